@@ -0,0 +1,150 @@
+use sep_40_oracle::{Asset, PriceData, PriceFeedClient};
+use soroban_fixed_point_math::SorobanFixedPoint;
+use soroban_sdk::{panic_with_error, Address, Env, Vec};
+use crate::constants::SCALAR_7;
+use crate::errors::OracleAggregatorError;
+use crate::storage;
+use crate::storage::AggregatorConfig;
+
+/// Validate the upstream oracles and cache their decimals. `prices` steps back through the records
+/// by the resolution, so every upstream oracle must report at the same resolution, which the
+/// aggregator reports as its own.
+///
+/// ### Panics
+/// If no source is given, a source is listed twice or the sources' resolutions differ
+pub(crate) fn load_sources(e: &Env, sources: &Vec<Address>) {
+    if sources.is_empty() {
+        panic_with_error!(e, OracleAggregatorError::InvalidSources);
+    }
+
+    let mut resolution = None;
+    for (index, source) in sources.iter().enumerate() {
+        if sources.first_index_of(&source) != Some(index as u32) {
+            panic_with_error!(e, OracleAggregatorError::InvalidSources);
+        }
+        let source_client = PriceFeedClient::new(e, &source);
+        storage::set_source_decimals(e, &source, source_client.decimals());
+        let source_resolution = source_client.resolution();
+        if *resolution.get_or_insert(source_resolution) != source_resolution {
+            panic_with_error!(e, OracleAggregatorError::InvalidSources);
+        }
+    }
+    storage::set_resolution(e, resolution.unwrap_or_default());
+}
+
+/// Validate an aggregator configuration against the number of upstream oracles
+///
+/// ### Panics
+/// If the quorum is 0 or above the number of sources, or the maximum age or spread is not above 0
+pub(crate) fn validate_config(e: &Env, config: &AggregatorConfig, source_count: u32) {
+    if config.quorum == 0 || config.quorum > source_count || config.max_age == 0 || config.max_spread <= 0 {
+        panic_with_error!(e, OracleAggregatorError::InvalidConfig);
+    }
+}
+
+/// Check if an asset is quoted by the aggregator
+pub(crate) fn is_quoted(e: &Env, asset: &Asset) -> bool {
+    storage::get_assets(e).contains(asset)
+}
+
+/// Convert a price with `from` decimals to `to` decimals
+pub(crate) fn convert_decimals(price: i128, from: u32, to: u32) -> i128 {
+    if from >= to {
+        price / 10i128.pow(from - to)
+    } else {
+        price * 10i128.pow(to - from)
+    }
+}
+
+/// Convert a price reported by an upstream oracle to the aggregator's decimals
+fn normalize(e: &Env, source: &Address, price_data: PriceData) -> PriceData {
+    PriceData {
+        price: convert_decimals(price_data.price, storage::get_source_decimals(e, source), storage::get_decimals(e)),
+        timestamp: price_data.timestamp,
+    }
+}
+
+/// Load the latest price of an asset from every upstream oracle that reports a fresh one.
+/// Oracles that fail, have no price for the asset or only a stale one are skipped.
+pub(crate) fn load_last_prices(e: &Env, asset: &Asset) -> Vec<PriceData> {
+    let config = storage::get_config(e);
+    let mut prices = Vec::new(e);
+    for source in storage::get_sources(e).iter() {
+        if let Ok(Ok(Some(price_data))) = PriceFeedClient::new(e, &source).try_lastprice(asset) {
+            if price_data.timestamp + config.max_age >= e.ledger().timestamp() {
+                prices.push_back(normalize(e, &source, price_data));
+            }
+        }
+    }
+    prices
+}
+
+/// Load the price of an asset at a timestamp from every upstream oracle that has a record for it.
+/// Oracles that fail or have no record are skipped.
+pub(crate) fn load_prices_at(e: &Env, asset: &Asset, timestamp: u64) -> Vec<PriceData> {
+    let mut prices = Vec::new(e);
+    for source in storage::get_sources(e).iter() {
+        if let Ok(Ok(Some(price_data))) = PriceFeedClient::new(e, &source).try_price(asset, &timestamp) {
+            prices.push_back(normalize(e, &source, price_data));
+        }
+    }
+    prices
+}
+
+/// Combine the upstream records of an asset at `records` timestamps, stepping back by the resolution
+/// from `latest`. Timestamps fewer oracles than the quorum have a record for are dropped.
+pub(crate) fn load_history(e: &Env, asset: &Asset, latest: u64, records: u32) -> Vec<PriceData> {
+    let resolution = storage::get_resolution(e) as u64;
+    let mut prices = Vec::new(e);
+    for index in 0..records as u64 {
+        let Some(timestamp) = latest.checked_sub(index * resolution) else {
+            break;
+        };
+        if let Some(price_data) = aggregate(e, &load_prices_at(e, asset, timestamp)) {
+            prices.push_back(price_data);
+        }
+    }
+    prices
+}
+
+/// Combine prices reported by the upstream oracles into their median. The timestamp is that of
+/// the oldest price used.
+///
+/// ### Returns
+/// The median price, or None if fewer prices than the quorum were reported
+///
+/// ### Panics
+/// If the spread between the highest and lowest price, relative to the median, is above the
+/// maximum spread
+pub(crate) fn aggregate(e: &Env, prices: &Vec<PriceData>) -> Option<PriceData> {
+    let config = storage::get_config(e);
+    if prices.len() < config.quorum {
+        return None;
+    }
+
+    let sorted = sort_by_price(e, prices);
+    let count = sorted.len();
+    let median = if count % 2 == 1 {
+        sorted.get_unchecked(count / 2).price
+    } else {
+        (sorted.get_unchecked(count / 2 - 1).price + sorted.get_unchecked(count / 2).price) / 2
+    };
+
+    let spread = sorted.last_unchecked().price - sorted.first_unchecked().price;
+    if median <= 0 || spread.fixed_div_ceil(e, &median, &SCALAR_7) > config.max_spread {
+        panic_with_error!(e, OracleAggregatorError::PriceDisagreement);
+    }
+
+    let timestamp = prices.iter().map(|price_data| price_data.timestamp).min().unwrap_or(0);
+    Some(PriceData { price: median, timestamp })
+}
+
+/// Sort prices from lowest to highest
+fn sort_by_price(e: &Env, prices: &Vec<PriceData>) -> Vec<PriceData> {
+    let mut sorted: Vec<PriceData> = Vec::new(e);
+    for price_data in prices.iter() {
+        let index = sorted.iter().position(|other| other.price > price_data.price).unwrap_or(sorted.len() as usize);
+        sorted.insert(index as u32, price_data);
+    }
+    sorted
+}
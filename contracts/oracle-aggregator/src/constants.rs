@@ -0,0 +1,3 @@
+/********** Numbers **********/
+/// Fixed-point scalar for 7 decimal numbers
+pub const SCALAR_7: i128 = 1_0000000;
@@ -0,0 +1,186 @@
+use sep_40_oracle::{Asset, PriceData, PriceFeedTrait};
+use soroban_sdk::{contract, contractclient, contractimpl, panic_with_error, Address, Env, Vec};
+use crate::errors::OracleAggregatorError;
+use crate::{aggregator, events, storage};
+use crate::storage::AggregatorConfig;
+
+#[contract]
+pub struct OracleAggregatorContract;
+
+#[contractclient(name = "OracleAggregatorClient")]
+pub trait OracleAggregator {
+    /// Initializes the aggregator contract
+    ///
+    /// # Arguments
+    /// * `admin` - The admin address
+    /// * `base` - The asset prices are quoted in, as reported by the upstream oracles
+    /// * `assets` - The assets the aggregator quotes
+    /// * `decimals` - The number of decimals of the aggregated prices
+    /// * `sources` - The addresses of the upstream SEP-40 oracles
+    /// * `config` - The quorum of fresh prices required, the maximum age of a price in seconds and
+    ///   the maximum spread between the highest and lowest price relative to the median, scaled by SCALAR_7
+    ///
+    /// # Panics
+    /// * If the contract is already initialized
+    /// * If no source is given, a source is listed twice or the sources' resolutions differ
+    /// * If the quorum is 0 or above the number of sources, or the maximum age or spread is not above 0
+    fn initialize(e: Env, admin: Address, base: Asset, assets: Vec<Asset>, decimals: u32, sources: Vec<Address>, config: AggregatorConfig);
+
+    /// Replaces the upstream oracles (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `sources` - The addresses of the upstream SEP-40 oracles
+    ///
+    /// # Panics
+    /// * If no source is given, a source is listed twice or the sources' resolutions differ
+    /// * If the quorum is above the number of sources
+    fn set_sources(e: Env, sources: Vec<Address>);
+
+    /// Retrieves the upstream oracles
+    fn get_sources(e: Env) -> Vec<Address>;
+
+    /// Sets the quorum, freshness and agreement requirements (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `config` - The quorum of fresh prices required, the maximum age of a price in seconds and
+    ///   the maximum spread between the highest and lowest price relative to the median, scaled by SCALAR_7
+    ///
+    /// # Panics
+    /// * If the quorum is 0 or above the number of sources, or the maximum age or spread is not above 0
+    fn set_config(e: Env, config: AggregatorConfig);
+
+    /// Retrieves the quorum, freshness and agreement requirements
+    fn get_config(e: Env) -> AggregatorConfig;
+
+    /// Retrieves the admin address
+    fn get_admin(e: Env) -> Address;
+}
+
+#[contractimpl]
+impl OracleAggregator for OracleAggregatorContract {
+    fn initialize(e: Env, admin: Address, base: Asset, assets: Vec<Asset>, decimals: u32, sources: Vec<Address>, config: AggregatorConfig) {
+        storage::extend_instance(&e);
+
+        if storage::is_init(&e) {
+            panic_with_error!(&e, OracleAggregatorError::AlreadyInitialized);
+        }
+
+        aggregator::load_sources(&e, &sources);
+        aggregator::validate_config(&e, &config, sources.len());
+
+        storage::set_base(&e, &base);
+        storage::set_assets(&e, &assets);
+        storage::set_decimals(&e, decimals);
+        storage::set_sources(&e, &sources);
+        storage::set_config(&e, &config);
+        storage::set_admin(&e, &admin);
+
+        events::initialize(&e, admin, base, assets, decimals, sources, config);
+    }
+
+    fn set_sources(e: Env, sources: Vec<Address>) {
+        storage::extend_instance(&e);
+
+        let admin = storage::get_admin(&e);
+        admin.require_auth();
+
+        // Forget the decimals of the oracles that are replaced
+        for source in storage::get_sources(&e).iter() {
+            if !sources.contains(&source) {
+                storage::remove_source_decimals(&e, &source);
+            }
+        }
+
+        aggregator::load_sources(&e, &sources);
+        aggregator::validate_config(&e, &storage::get_config(&e), sources.len());
+        storage::set_sources(&e, &sources);
+        events::set_sources(&e, admin, sources);
+    }
+
+    fn get_sources(e: Env) -> Vec<Address> {
+        storage::extend_instance(&e);
+        storage::get_sources(&e)
+    }
+
+    fn set_config(e: Env, config: AggregatorConfig) {
+        storage::extend_instance(&e);
+
+        let admin = storage::get_admin(&e);
+        admin.require_auth();
+
+        aggregator::validate_config(&e, &config, storage::get_sources(&e).len());
+        storage::set_config(&e, &config);
+        events::set_config(&e, admin, config);
+    }
+
+    fn get_config(e: Env) -> AggregatorConfig {
+        storage::extend_instance(&e);
+        storage::get_config(&e)
+    }
+
+    fn get_admin(e: Env) -> Address {
+        storage::extend_instance(&e);
+        storage::get_admin(&e)
+    }
+}
+
+#[contractimpl]
+impl PriceFeedTrait for OracleAggregatorContract {
+    fn base(e: Env) -> Asset {
+        storage::extend_instance(&e);
+        storage::get_base(&e)
+    }
+
+    fn assets(e: Env) -> Vec<Asset> {
+        storage::extend_instance(&e);
+        storage::get_assets(&e)
+    }
+
+    fn decimals(e: Env) -> u32 {
+        storage::extend_instance(&e);
+        storage::get_decimals(&e)
+    }
+
+    fn resolution(e: Env) -> u32 {
+        storage::extend_instance(&e);
+        storage::get_resolution(&e)
+    }
+
+    /// Returns the median of the upstream oracles' records at `timestamp`, or None if the asset is not
+    /// quoted or fewer oracles than the quorum have a record
+    fn price(e: Env, asset: Asset, timestamp: u64) -> Option<PriceData> {
+        storage::extend_instance(&e);
+        if !aggregator::is_quoted(&e, &asset) {
+            return None;
+        }
+        aggregator::aggregate(&e, &aggregator::load_prices_at(&e, &asset, timestamp))
+    }
+
+    /// Returns the median records at up to `records` timestamps, newest first. Starting at the
+    /// timestamp of the latest price, it steps back by the resolution and combines the upstream
+    /// records at each timestamp. Timestamps fewer oracles than the quorum have a record for are left
+    /// out. Returns None if the asset is not quoted or there is no latest price.
+    ///
+    /// Panics if the prices at a timestamp disagree by more than the maximum spread
+    fn prices(e: Env, asset: Asset, records: u32) -> Option<Vec<PriceData>> {
+        storage::extend_instance(&e);
+        if !aggregator::is_quoted(&e, &asset) {
+            return None;
+        }
+
+        let latest = aggregator::aggregate(&e, &aggregator::load_last_prices(&e, &asset))?;
+        Some(aggregator::load_history(&e, &asset, latest.timestamp, records))
+    }
+
+    /// Returns the median of the upstream oracles' fresh prices, or None if the asset is not quoted or
+    /// fewer fresh oracles than the quorum report a price
+    ///
+    /// Panics if the prices disagree by more than the maximum spread
+    fn lastprice(e: Env, asset: Asset) -> Option<PriceData> {
+        storage::extend_instance(&e);
+        if !aggregator::is_quoted(&e, &asset) {
+            return None;
+        }
+        aggregator::aggregate(&e, &aggregator::load_last_prices(&e, &asset))
+    }
+}
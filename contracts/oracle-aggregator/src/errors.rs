@@ -0,0 +1,12 @@
+use soroban_sdk::contracterror;
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum OracleAggregatorError {
+    NotInitialized = 700,
+    AlreadyInitialized = 701,
+    InvalidSources = 702,
+    InvalidConfig = 703,
+    PriceDisagreement = 704,
+}
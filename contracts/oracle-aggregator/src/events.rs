@@ -0,0 +1,33 @@
+use sep_40_oracle::Asset;
+use soroban_sdk::{symbol_short, Address, Env, Symbol, Vec};
+use crate::storage::AggregatorConfig;
+
+/// Tag and version of the event schema, the first topic of every event
+const EVENT_TAG: Symbol = symbol_short!("agg_v1");
+
+/// Emitted when the aggregator is initialized
+///
+/// - topics - `["agg_v1", "initialize", admin: Address]`
+/// - data - `[base: Asset, assets: Vec<Asset>, decimals: u32, sources: Vec<Address>, config: AggregatorConfig]`
+pub fn initialize(e: &Env, admin: Address, base: Asset, assets: Vec<Asset>, decimals: u32, sources: Vec<Address>, config: AggregatorConfig) {
+    let topics = (EVENT_TAG, Symbol::new(e, "initialize"), admin);
+    e.events().publish(topics, (base, assets, decimals, sources, config));
+}
+
+/// Emitted when the admin replaces the upstream oracles
+///
+/// - topics - `["agg_v1", "set_sources", admin: Address]`
+/// - data - `[sources: Vec<Address>]`
+pub fn set_sources(e: &Env, admin: Address, sources: Vec<Address>) {
+    let topics = (EVENT_TAG, Symbol::new(e, "set_sources"), admin);
+    e.events().publish(topics, sources);
+}
+
+/// Emitted when the admin updates the quorum, freshness and agreement requirements
+///
+/// - topics - `["agg_v1", "set_config", admin: Address]`
+/// - data - `[config: AggregatorConfig]`
+pub fn set_config(e: &Env, admin: Address, config: AggregatorConfig) {
+    let topics = (EVENT_TAG, Symbol::new(e, "set_config"), admin);
+    e.events().publish(topics, config);
+}
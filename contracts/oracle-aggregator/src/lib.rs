@@ -0,0 +1,12 @@
+#![no_std]
+#[cfg(any(test, feature = "testutils"))]
+extern crate std;
+
+mod storage;
+mod contract;
+mod errors;
+mod constants;
+mod aggregator;
+mod events;
+
+pub use contract::*;
@@ -0,0 +1,157 @@
+use sep_40_oracle::Asset;
+use soroban_sdk::{contracttype, Address, Env, Vec};
+use soroban_sdk::unwrap::UnwrapOptimized;
+
+const ONE_DAY_LEDGERS: u32 = 17280; // assumes 5s a ledger
+
+const LEDGER_THRESHOLD_INSTANCE: u32 = ONE_DAY_LEDGERS * 30; // ~ 30 days
+const LEDGER_BUMP_INSTANCE: u32 = LEDGER_THRESHOLD_INSTANCE + ONE_DAY_LEDGERS; // ~ 31 days
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    Admin,
+    Base,
+    Assets,
+    Decimals,
+    Resolution,
+    Sources,
+    SourceDecimals(Address),
+    Config,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct AggregatorConfig {
+    pub quorum: u32,
+    pub max_age: u64,
+    pub max_spread: i128,
+}
+
+/// Bump the instance rent for the contract
+pub fn extend_instance(e: &Env) {
+    e.storage()
+        .instance()
+        .extend_ttl(LEDGER_THRESHOLD_INSTANCE, LEDGER_BUMP_INSTANCE);
+}
+
+/// Check if the contract has been initialized
+pub fn is_init(e: &Env) -> bool {
+    e.storage().instance().has(&DataKey::Admin)
+}
+
+/// Fetch the current admin Address
+pub fn get_admin(e: &Env) -> Address {
+    e.storage().instance().get(&DataKey::Admin).unwrap_optimized()
+}
+
+/// Set a new admin
+///
+/// ### Arguments
+/// * `new_admin` - The Address for the admin
+pub fn set_admin(e: &Env, new_admin: &Address) {
+    e.storage().instance().set(&DataKey::Admin, new_admin);
+}
+
+/// Fetch the asset prices are quoted in
+pub fn get_base(e: &Env) -> Asset {
+    e.storage().instance().get(&DataKey::Base).unwrap_optimized()
+}
+
+/// Set the asset prices are quoted in
+///
+/// ### Arguments
+/// * `base` - The base asset
+pub fn set_base(e: &Env, base: &Asset) {
+    e.storage().instance().set(&DataKey::Base, base);
+}
+
+/// Fetch the assets the aggregator quotes
+pub fn get_assets(e: &Env) -> Vec<Asset> {
+    e.storage().instance().get(&DataKey::Assets).unwrap_optimized()
+}
+
+/// Set the assets the aggregator quotes
+///
+/// ### Arguments
+/// * `assets` - The quoted assets
+pub fn set_assets(e: &Env, assets: &Vec<Asset>) {
+    e.storage().instance().set(&DataKey::Assets, assets);
+}
+
+/// Fetch the number of decimals of the aggregated prices
+pub fn get_decimals(e: &Env) -> u32 {
+    e.storage().instance().get(&DataKey::Decimals).unwrap_optimized()
+}
+
+/// Set the number of decimals of the aggregated prices
+///
+/// ### Arguments
+/// * `decimals` - The decimals of the aggregated prices
+pub fn set_decimals(e: &Env, decimals: u32) {
+    e.storage().instance().set(&DataKey::Decimals, &decimals);
+}
+
+/// Fetch the number of seconds between price records
+pub fn get_resolution(e: &Env) -> u32 {
+    e.storage().instance().get(&DataKey::Resolution).unwrap_optimized()
+}
+
+/// Set the number of seconds between price records
+///
+/// ### Arguments
+/// * `resolution` - The resolution in seconds
+pub fn set_resolution(e: &Env, resolution: u32) {
+    e.storage().instance().set(&DataKey::Resolution, &resolution);
+}
+
+/// Fetch the upstream oracles
+pub fn get_sources(e: &Env) -> Vec<Address> {
+    e.storage().instance().get(&DataKey::Sources).unwrap_optimized()
+}
+
+/// Set the upstream oracles
+///
+/// ### Arguments
+/// * `sources` - The addresses of the upstream oracles
+pub fn set_sources(e: &Env, sources: &Vec<Address>) {
+    e.storage().instance().set(&DataKey::Sources, sources);
+}
+
+/// Fetch the number of decimals of an upstream oracle's prices
+///
+/// ### Arguments
+/// * `source` - The address of the upstream oracle
+pub fn get_source_decimals(e: &Env, source: &Address) -> u32 {
+    e.storage().instance().get(&DataKey::SourceDecimals(source.clone())).unwrap_optimized()
+}
+
+/// Set the number of decimals of an upstream oracle's prices
+///
+/// ### Arguments
+/// * `source` - The address of the upstream oracle
+/// * `decimals` - The decimals reported by the oracle
+pub fn set_source_decimals(e: &Env, source: &Address, decimals: u32) {
+    e.storage().instance().set(&DataKey::SourceDecimals(source.clone()), &decimals);
+}
+
+/// Remove the cached number of decimals of an upstream oracle that is no longer used
+///
+/// ### Arguments
+/// * `source` - The address of the upstream oracle
+pub fn remove_source_decimals(e: &Env, source: &Address) {
+    e.storage().instance().remove(&DataKey::SourceDecimals(source.clone()));
+}
+
+/// Fetch the quorum, freshness and agreement requirements
+pub fn get_config(e: &Env) -> AggregatorConfig {
+    e.storage().instance().get(&DataKey::Config).unwrap_optimized()
+}
+
+/// Set the quorum, freshness and agreement requirements
+///
+/// ### Arguments
+/// * `config` - The AggregatorConfig to set
+pub fn set_config(e: &Env, config: &AggregatorConfig) {
+    e.storage().instance().set(&DataKey::Config, config);
+}
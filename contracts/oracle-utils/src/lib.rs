@@ -0,0 +1,42 @@
+#![no_std]
+
+use sep_40_oracle::PriceData;
+use soroban_fixed_point_math::SorobanFixedPoint;
+use soroban_sdk::{Env, Vec};
+
+/// Fixed-point scalar for 7 decimal numbers
+pub const SCALAR_7: i128 = 1_0000000;
+/// Number of decimals of SCALAR_7, prices are normalized to it
+pub const DECIMALS_7: u32 = 7;
+
+/// Calculate how far a price moved from a previous price, scaled by SCALAR_7
+pub fn calculate_deviation(e: &Env, price: i128, previous_price: i128) -> i128 {
+    (price - previous_price).abs().fixed_div_ceil(e, &previous_price, &SCALAR_7)
+}
+
+/// Convert a price with `decimals` decimals to 7 decimals
+pub fn normalize_price(price: i128, decimals: u32) -> i128 {
+    if decimals >= DECIMALS_7 {
+        price / 10i128.pow(decimals - DECIMALS_7)
+    } else {
+        price * 10i128.pow(DECIMALS_7 - decimals)
+    }
+}
+
+/// Calculate the average of the latest `records` prices of an oracle history. SEP-40 oracles record
+/// prices at a fixed resolution, so this is their time-weighted average over the window.
+/// Falls back to the spot price if the oracle keeps no history.
+pub fn calculate_twap(history: &Option<Vec<PriceData>>, records: u32, spot_price: i128) -> i128 {
+    let mut sum = 0;
+    let mut count = 0;
+    if let Some(history) = history {
+        for price_data in history.iter().take(records as usize) {
+            sum += price_data.price;
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return spot_price;
+    }
+    sum / count
+}
@@ -1,3 +1,26 @@
 /********** Numbers **********/
 /// Fixed-point scalar for 7 decimal numbers
-pub const SCALAR_7: i128 = 1_0000000;
\ No newline at end of file
+pub const SCALAR_7: i128 = 1_0000000;
+/// Number of decimals of SCALAR_7, prices and values are normalized to it
+pub const DECIMALS_7: u32 = 7;
+
+/********** Fees **********/
+/// Base fee charged on deposits and withdrawals, scaled by SCALAR_7
+pub const BASE_LIQUIDITY_FEE: i128 = 0_0030000;
+/// Maximum fee added for pushing a token away from its target ratio, scaled by SCALAR_7
+pub const TAX_LIQUIDITY_FEE: i128 = 0_0050000;
+
+/********** Borrowing **********/
+/// Default maximum share of each token's supply that can be borrowed, scaled by SCALAR_7
+pub const DEFAULT_MAX_UTILIZATION: i128 = 0_9000000;
+
+/********** Oracle **********/
+/// Default maximum age of a price in seconds. Liquidity providers are not levered, so the pool
+/// accepts older prices than the position manager.
+pub const DEFAULT_MAX_PRICE_AGE: u64 = 24 * 60 * 60;
+/// Default maximum move of a price from the previous oracle record, scaled by SCALAR_7
+pub const DEFAULT_MAX_PRICE_DEVIATION: i128 = 0_5000000;
+/// Default number of oracle records averaged into a TWAP, a single record makes the TWAP the spot price
+pub const DEFAULT_TWAP_RECORDS: u32 = 1;
+/// Maximum number of oracle records averaged into a TWAP
+pub const MAX_TWAP_RECORDS: u32 = 20;
@@ -1,11 +1,11 @@
-use sep_40_oracle::{Asset, PriceFeedClient};
 use soroban_sdk::{contract, contractclient, Address, Env, contractimpl, token, panic_with_error, Symbol, Vec, Val, IntoVal, vec, log};
 use soroban_sdk::auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation};
 use soroban_sdk::unwrap::UnwrapOptimized;
-use crate::constants::SCALAR_7;
+use crate::constants::{MAX_TWAP_RECORDS, SCALAR_7};
 use crate::errors::PoolError;
-use crate::{oracle, storage};
-use crate::storage::TokenInfo;
+use crate::oracle::PriceMode;
+use crate::{events, oracle, pool, storage};
+use crate::storage::{DepositQuote, PriceConfig, TokenInfo, WithdrawQuote};
 use soroban_fixed_point_math::SorobanFixedPoint;
 use sep_41_token::StellarAssetClient;
 
@@ -21,54 +21,119 @@ pub trait Pool {
     /// * `oracle` - The oracle contract address
     /// * `position_manager` - The position manager contract address
     /// * `spl` - The SLP token contract address
-    /// * `token_a` - The address of token A
-    /// * `token_b` - The address of token B
+    /// * `tokens` - The pool tokens and their target ratios
     ///
     /// # Panics
     /// * If the contract is already initialized
+    /// * If fewer than two tokens are listed or a token is listed twice
     /// * If the initial token supplies are not zero
-    /// * If the target ratios of token A and token B do not sum to SCALAR_7
-    fn initialize(e: Env, admin: Address, oracle: Address, position_manager: Address, spl: Address, token_a: TokenInfo, token_b: TokenInfo);
+    /// * If the target ratios do not sum to SCALAR_7
+    fn initialize(e: Env, admin: Address, oracle: Address, position_manager: Address, spl: Address, tokens: Vec<TokenInfo>);
 
     /// Deposits liquidity into the pool
     ///
     /// # Arguments
     /// * `user` - The address of the user depositing
-    /// * `token_a_amount` - The amount of token A to deposit
-    /// * `token_b_amount` - The amount of token B to deposit
+    /// * `amounts` - The amount of each pool token to deposit, in listing order
+    /// * `min_slp_out` - The minimum amount of SLP tokens to mint
     ///
     /// # Returns
-    /// The amount of SLP tokens minted to the user
+    /// The amount of SLP tokens minted to the user, net of the deposit fee. Deposits pushing a token
+    /// away from its target ratio pay a higher fee, deposits rebalancing the pool get a discount.
     ///
     /// # Panics
-    /// * If the deposit does not improve the token ratio towards the target ratio
-    fn deposit(e: Env, user: Address, token_a_amount: i128, token_b_amount: i128) -> i128;
+    /// * If the number of amounts does not match the number of pool tokens
+    /// * If any amount is negative
+    /// * If the first deposit is not at the target ratios
+    /// * If the unrealized profit of traders leaves the pool without any value to mint SLP tokens against
+    /// * If the amount of SLP tokens minted is below `min_slp_out`
+    fn deposit(e: Env, user: Address, amounts: Vec<i128>, min_slp_out: i128) -> i128;
 
     /// Withdraws liquidity from the pool
     ///
     /// # Arguments
     /// * `user` - The address of the user withdrawing
     /// * `slp_amount` - The amount of SLP tokens to burn
+    /// * `min_amounts_out` - The minimum amount of each pool token to receive, in listing order
     ///
     /// # Returns
-    /// A tuple containing the amounts of token A and token B withdrawn
+    /// The amount of each pool token withdrawn, in listing order, net of the withdrawal fee
     ///
     /// # Panics
+    /// * If the number of minimum amounts does not match the number of pool tokens
     /// * If there are insufficient funds in the pool for the withdrawal
-    fn withdraw(e: Env, user: Address, slp_amount: i128) -> (i128, i128);
+    /// * If the amount of any pool token withdrawn is below its minimum amount
+    fn withdraw(e: Env, user: Address, slp_amount: i128, min_amounts_out: Vec<i128>) -> Vec<i128>;
+
+    /// Withdraws liquidity from the pool in a single token
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user withdrawing
+    /// * `slp_amount` - The amount of SLP tokens to burn
+    /// * `token` - The address of the pool token to withdraw
+    /// * `min_amount_out` - The minimum amount of the token to receive
+    ///
+    /// # Returns
+    /// The amount of the token withdrawn, net of the withdrawal fee
+    ///
+    /// # Panics
+    /// * If the token address is invalid
+    /// * If there are insufficient funds in the pool for the withdrawal
+    /// * If the amount withdrawn is below `min_amount_out`
+    /// * If the payout exceeds the amount of the token not borrowed by the position manager
+    fn withdraw_single(e: Env, user: Address, slp_amount: i128, token: Address, min_amount_out: i128) -> i128;
+
+    /// Swaps one pool token for another at the oracle price
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user swapping
+    /// * `token_in` - The address of the pool token sent to the pool
+    /// * `token_out` - The address of the pool token received from the pool
+    /// * `amount_in` - The amount of `token_in` to swap
+    /// * `min_amount_out` - The minimum amount of `token_out` to receive
+    ///
+    /// # Returns
+    /// The amount of `token_out` received, net of the swap fee. Swaps pushing either token away from
+    /// its target ratio pay a higher fee, swaps rebalancing the pool get a discount.
+    ///
+    /// # Panics
+    /// * If either token address is invalid or both are the same token
+    /// * If the amount in is not positive
+    /// * If the amount out is below `min_amount_out`
+    /// * If the amount out exceeds the amount of `token_out` not borrowed by the position manager
+    fn swap(e: Env, user: Address, token_in: Address, token_out: Address, amount_in: i128, min_amount_out: i128) -> i128;
+
+    /// Previews a deposit without moving any tokens
+    ///
+    /// # Arguments
+    /// * `amounts` - The amount of each pool token to deposit, in listing order
+    ///
+    /// # Returns
+    /// The SLP tokens the deposit would mint, the fee value charged and the token ratios after the deposit
+    fn quote_deposit(e: Env, amounts: Vec<i128>) -> DepositQuote;
+
+    /// Previews a withdrawal without moving any tokens
+    ///
+    /// # Arguments
+    /// * `slp_amount` - The amount of SLP tokens to burn
+    ///
+    /// # Returns
+    /// The amount of each pool token the withdrawal would pay out, the fee value charged and the token
+    /// ratios after the withdrawal
+    fn quote_withdraw(e: Env, slp_amount: i128) -> WithdrawQuote;
 
     /// Borrows assets from the pool (only callable by Position Manager)
     ///
     /// # Arguments
     /// * `token` - The address of the token to borrow
     /// * `amount` - The amount of the token to borrow
-    /// * `fee` - The fee to pay for borrowing
+    /// * `fee` - The fee to pay for borrowing, transferred from the position manager to the pool
     ///
     /// # Panics
     /// * If called by an address other than the position manager
     /// * If the token address is invalid
     /// * If there's insufficient liquidity in the pool
-    /// * If borrowing would leave less than 10% of the total supply in the pool
+    /// * If borrowing would push the token's utilization above the maximum utilization
     fn borrow(e: Env, token: Address, amount: i128, fee: i128);
 
     /// Repays borrowed assets to the pool (only callable by Position Manager)
@@ -77,11 +142,12 @@ pub trait Pool {
     /// * `token` - The address of the token to repay
     /// * `amount` - The amount of the token to repay
     /// * `fee` - The fee to pay for repayment
+    /// * `borrowed` - The amount originally borrowed that is settled, released from the reserved liquidity
     ///
     /// # Panics
     /// * If called by an address other than the position manager
     /// * If the token address is invalid
-    fn repay(e: Env, token: Address, amount: i128, fee: i128);
+    fn repay(e: Env, token: Address, amount: i128, fee: i128, borrowed: i128);
 
     /// Retrieves the oracle address
     ///
@@ -106,11 +172,102 @@ pub trait Pool {
     /// # Panics
     /// * If the token address is invalid
     fn get_token_info(e: Env, token: Address) -> TokenInfo;
+
+    /// Retrieves the TokenInfo of every pool token, in listing order
+    fn get_tokens(e: Env) -> Vec<TokenInfo>;
+
+    /// Retrieves the share of a token's supply reserved by the position manager
+    ///
+    /// # Arguments
+    /// * `token` - The address of the token
+    ///
+    /// # Returns
+    /// The utilization of the token, scaled by SCALAR_7
+    ///
+    /// # Panics
+    /// * If the token address is invalid
+    fn get_utilization(e: Env, token: Address) -> i128;
+
+    /// Retrieves the maximum share of each token's supply that can be borrowed, scaled by SCALAR_7
+    fn get_max_utilization(e: Env) -> i128;
+
+    /// Retrieves the oracle limits for an asset, the maximum age of its price in seconds and the
+    /// maximum move from the previous oracle record
+    ///
+    /// # Arguments
+    /// * `asset` - The address of the asset
+    fn get_price_config(e: Env, asset: Address) -> PriceConfig;
+
+    /// Reconciles the pool's bookkeeping with its actual token balances without changing either
+    ///
+    /// # Returns
+    /// The gap between each pool token's balance and its total supply less the reserved amount, in
+    /// listing order. A positive gap is held by the pool but not accounted for, a negative gap is
+    /// accounted for but missing.
+    fn sync(e: Env) -> Vec<i128>;
+
+    /// Proposes a new admin (only callable by the admin). The new admin takes over once it accepts.
+    ///
+    /// # Arguments
+    /// * `new_admin` - The address proposed as the new admin
+    fn set_admin(e: Env, new_admin: Address);
+
+    /// Accepts the admin role (only callable by the proposed admin)
+    ///
+    /// # Panics
+    /// * If no admin has been proposed
+    fn accept_admin(e: Env);
+
+    /// Retrieves the admin address
+    fn get_admin(e: Env) -> Address;
+
+    /// Sets the oracle (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `oracle` - The new oracle contract address
+    fn set_oracle(e: Env, oracle: Address);
+
+    /// Sets the position manager allowed to borrow and repay (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `position_manager` - The new position manager contract address
+    fn set_position_manager(e: Env, position_manager: Address);
+
+    /// Sets the target ratios of the pool tokens (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `ratios` - The target ratio of each pool token, in listing order
+    ///
+    /// # Panics
+    /// * If the number of ratios does not match the number of pool tokens
+    /// * If the target ratios do not sum to SCALAR_7
+    fn set_target_ratios(e: Env, ratios: Vec<u32>);
+
+    /// Sets the maximum share of each token's supply that can be borrowed (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `max_utilization` - The maximum utilization, scaled by SCALAR_7
+    ///
+    /// # Panics
+    /// * If the maximum utilization is not above 0 and below SCALAR_7
+    fn set_max_utilization(e: Env, max_utilization: i128);
+
+    /// Sets the oracle limits for an asset (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `asset` - The address of the asset
+    /// * `config` - The maximum age of the asset's price in seconds, its maximum move from the previous
+    ///   oracle record, scaled by SCALAR_7, and the number of oracle records averaged into its TWAP
+    ///
+    /// # Panics
+    /// * If the maximum age or the maximum deviation is not above 0
+    /// * If the number of TWAP records is 0 or above MAX_TWAP_RECORDS
+    fn set_price_config(e: Env, asset: Address, config: PriceConfig);
 }
 
 #[contractimpl]
 impl Pool for PoolContract {
-    fn initialize(e: Env, admin: Address, oracle: Address, position_manager: Address, spl: Address, token_a: TokenInfo, token_b: TokenInfo) {
+    fn initialize(e: Env, admin: Address, oracle: Address, position_manager: Address, spl: Address, tokens: Vec<TokenInfo>) {
         storage::extend_instance(&e);
 
         if storage::is_init(&e) {
@@ -120,23 +277,37 @@ impl Pool for PoolContract {
         storage::set_slp_token(&e, &spl);
         storage::set_slp_supply(&e, 0);
 
-        if token_a.total_supply != 0 || token_b.total_supply != 0 {
-            panic_with_error!(&e, PoolError::InvalidTokenSupply);
+        if tokens.len() < 2 {
+            panic_with_error!(&e, PoolError::InvalidTokenCount);
         }
 
-        if token_a.target_ratio.checked_add(token_b.target_ratio).unwrap_optimized() != SCALAR_7 as u32 {
-            panic_with_error!(&e, PoolError::InvalidTargetRatio);
+        let mut ratios = Vec::new(&e);
+        for (index, token_info) in tokens.iter().enumerate() {
+            if token_info.total_supply != 0 || token_info.reserved != 0 {
+                panic_with_error!(&e, PoolError::InvalidTokenSupply);
+            }
+            if pool::find_token(&e, &tokens, &token_info.address) != index as u32 {
+                panic_with_error!(&e, PoolError::InvalidTokenAddress);
+            }
+            ratios.push_back(token_info.target_ratio);
         }
+        pool::validate_target_ratios(&e, &ratios);
 
-        storage::set_token_a_info(&e, &token_a);
-        storage::set_token_b_info(&e, &token_b);
+        storage::set_tokens(&e, &tokens);
+        for token_info in tokens.iter() {
+            let decimals = token::Client::new(&e, &token_info.address).decimals();
+            storage::set_token_decimals(&e, &token_info.address, decimals);
+        }
 
         storage::set_position_manager(&e, &position_manager);
         storage::set_oracle(&e, &oracle);
+        storage::set_oracle_decimals(&e, oracle::load_decimals(&e, &oracle));
         storage::set_admin(&e, &admin);
+
+        events::initialize(&e, admin, oracle, position_manager, spl, tokens);
     }
 
-    fn deposit(e: Env, user: Address, token_a_amount: i128, token_b_amount: i128) -> i128 {
+    fn deposit(e: Env, user: Address, amounts: Vec<i128>, min_slp_out: i128) -> i128 {
         storage::extend_instance(&e);
         user.require_auth();
 
@@ -144,101 +315,31 @@ impl Pool for PoolContract {
             panic_with_error!(&e, PoolError::NotInitialized);
         }
 
-        let mut token_a_info = storage::get_token_a_info(&e);
-        let mut token_b_info = storage::get_token_b_info(&e);
-
-        let oracle = storage::get_oracle(&e);
-        let token_a_price = oracle::load_price(&e, oracle.clone(), token_a_info.address.clone());
-        let token_b_price = oracle::load_price(&e, oracle.clone(), token_b_info.address.clone());
+        let mut tokens = storage::get_tokens(&e);
+        let quote = pool::calculate_deposit(&e, &tokens, &amounts);
+        let slp_to_mint = quote.slp_out;
 
-        log!(&e, "a_price: {} b_price{}", token_a_price, token_b_price);
-
-        // Calculate current pool value
-        let a_value = token_a_info.total_supply.fixed_mul_floor(&e, &token_a_price, &SCALAR_7);
-        let b_value = token_b_info.total_supply.fixed_mul_floor(&e, &token_b_price, &SCALAR_7);
-        let total_value = a_value + b_value;
-
-        log!(&e, "a_value: {} b_value: {} total: {}", a_value, b_value, total_value);
-
-        // Calculate deposit value divide by SCALAR_7 since both are scaled by SCALAR_7
-        let deposit_a_value = token_a_amount.fixed_mul_floor(&e, &token_a_price, &SCALAR_7);
-        let deposit_b_value = token_b_amount.fixed_mul_floor(&e, &token_b_price, &SCALAR_7);
-        let deposit_total_value = deposit_a_value + deposit_b_value;
-
-        log!(&e, "deposit_a_value: {} deposit_b_value: {} deposit_total_value: {}", deposit_a_value, deposit_b_value, deposit_total_value);
-
-        let (a_ratio, b_ratio) = if total_value == 0 {
-            // First deposit, use the deposit amounts to calculate initial ratios
-            let a_ratio = deposit_a_value.fixed_div_floor(&e, &deposit_total_value, &SCALAR_7);
-            (a_ratio, SCALAR_7 - a_ratio)
-        } else {
-            let a_ratio = a_value.fixed_div_floor(&e, &total_value, &SCALAR_7);
-            (a_ratio, SCALAR_7 - a_ratio)
-        };
-
-        log!(&e, "a_ratio: {} b_ratio", a_ratio, b_ratio);
-
-        let new_total_value = total_value + deposit_total_value;
-
-        // Calculate new ratios after deposit
-        let (new_a_ratio, new_b_ratio) = if total_value == 0 {
-            // First deposit, new ratios are the same as current (initial) ratios
-            (a_ratio, b_ratio)
-        } else {
-            let new_a_value = a_value + deposit_a_value;
-            let new_a_ratio = new_a_value.fixed_div_floor(&e, &new_total_value, &SCALAR_7);
-            (new_a_ratio, SCALAR_7 - new_a_ratio)
-        };
-
-        log!(&e, "new_a_ratio: {} new_b_ratio: {}", new_a_ratio, new_b_ratio);
-
-        // Check if deposit improves the target ratio
-        if total_value != 0 {
-            let target_a_ratio = token_a_info.target_ratio as i128;
-            let target_b_ratio = token_b_info.target_ratio as i128;
-
-            let a_diff = (a_ratio - target_a_ratio).abs();
-            let new_a_diff = (new_a_ratio - target_a_ratio).abs();
-            let b_diff = (b_ratio - target_b_ratio).abs();
-            let new_b_diff = (new_b_ratio - target_b_ratio).abs();
+        if slp_to_mint < min_slp_out {
+            panic_with_error!(&e, PoolError::InsufficientSlpOut);
+        }
 
-            log!(&e, "current_a_diff: {} new_a_diff: {}", a_diff, new_a_diff);
-            log!(&e, "current_b_diff: {} new_b_diff: {}", b_diff, new_b_diff);
+        // The full deposit, including the fee, is added to the pool
+        for (index, amount) in amounts.iter().enumerate() {
+            let mut token_info = tokens.get_unchecked(index as u32);
+            token_info.total_supply += amount;
 
-            if new_a_diff > a_diff || new_b_diff > b_diff {
-                panic_with_error!(&e, PoolError::DepositDoesNotImproveRatio);
-            }
-        } else {
-            if a_ratio != token_a_info.target_ratio as i128 || b_ratio != token_b_info.target_ratio as i128 {
-                panic_with_error!(&e, PoolError::DepositDoesNotImproveRatio);
+            // Transfer tokens from user to the contract
+            if amount > 0 {
+                let token_client = token::Client::new(&e, &token_info.address);
+                token_client.transfer(&user, &e.current_contract_address(), &amount);
             }
+            tokens.set(index as u32, token_info);
         }
 
-        // Deposit improves the ratio, proceed with the deposit
-        token_a_info.total_supply = token_a_info.total_supply + token_a_amount;
-        token_b_info.total_supply = token_b_info.total_supply + token_b_amount;
-
         // Update storage
-        storage::set_token_a_info(&e, &token_a_info);
-        storage::set_token_b_info(&e, &token_b_info);
-
-        // Transfer tokens from user to the contract
-        let token_a_client = token::Client::new(&e, &token_a_info.address.clone());
-        let token_b_client = token::Client::new(&e, &token_b_info.address.clone());
-        token_a_client.transfer(&user, &e.current_contract_address(), &token_a_amount);
-        token_b_client.transfer(&user, &e.current_contract_address(), &token_b_amount);
-
-        // Mint SLP tokens
-        let current_slp_supply = storage::get_slp_supply(&e);
-        let slp_to_mint = if current_slp_supply == 0 {
-            // If it's the first deposit, mint deposit_total_value of SLP
-            deposit_total_value
-        } else {
-            // Calculate proportional amount of SLP to mint
-            deposit_total_value.fixed_mul_floor(&e, &current_slp_supply, &SCALAR_7).fixed_div_floor(&e, &total_value, &SCALAR_7)
-        };
+        storage::set_tokens(&e, &tokens);
 
-        let new_slp_supply = current_slp_supply + slp_to_mint;
+        let new_slp_supply = storage::get_slp_supply(&e) + slp_to_mint;
         storage::set_slp_supply(&e, new_slp_supply);
 
         // Mint SLP tokens to the user
@@ -248,10 +349,11 @@ impl Pool for PoolContract {
 
         log!(&e, "slp_to_mint: {}", slp_to_mint);
 
+        events::deposit(&e, user, amounts, slp_to_mint, quote.fee_value, new_slp_supply);
         slp_to_mint
     }
 
-    fn withdraw(e: Env, user: Address, slp_amount: i128) -> (i128, i128) {
+    fn withdraw(e: Env, user: Address, slp_amount: i128, min_amounts_out: Vec<i128>) -> Vec<i128> {
         storage::extend_instance(&e);
         user.require_auth();
 
@@ -259,92 +361,180 @@ impl Pool for PoolContract {
             panic_with_error!(&e, PoolError::NotInitialized);
         }
 
-        let mut token_a_info = storage::get_token_a_info(&e);
-        let mut token_b_info = storage::get_token_b_info(&e);
+        let mut tokens = storage::get_tokens(&e);
+        if min_amounts_out.len() != tokens.len() {
+            panic_with_error!(&e, PoolError::InvalidTokenCount);
+        }
 
-        let oracle = storage::get_oracle(&e);
-        let token_a_price = oracle::load_price(&e, oracle.clone(), token_a_info.address.clone());
-        let token_b_price = oracle::load_price(&e, oracle.clone(), token_b_info.address.clone());
+        let quote = pool::calculate_withdraw(&e, &tokens, slp_amount);
+        for (index, withdraw_amount) in quote.amounts.iter().enumerate() {
+            if withdraw_amount < min_amounts_out.get_unchecked(index as u32) {
+                panic_with_error!(&e, PoolError::InsufficientWithdrawOut);
+            }
 
-        // Calculate current pool value
-        let a_value = token_a_info.total_supply.fixed_mul_floor(&e, &token_a_price, &SCALAR_7);
-        let b_value = token_b_info.total_supply.fixed_mul_floor(&e, &token_b_price, &SCALAR_7);
-        let total_value = a_value + b_value;
+            // Update token supplies
+            let mut token_info = tokens.get_unchecked(index as u32);
+            token_info.total_supply -= withdraw_amount;
+            tokens.set(index as u32, token_info);
+        }
+        storage::set_tokens(&e, &tokens);
 
-        log!(&e, "a_value: {} b_value: {} total_value: {}", a_value, b_value, total_value);
+        // Burn SLP tokens
+        let new_slp_supply = storage::get_slp_supply(&e) - slp_amount;
+        storage::set_slp_supply(&e, new_slp_supply);
 
-        // Calculate USD value to withdraw
-        let current_slp_supply = storage::get_slp_supply(&e);
-        let withdraw_ratio = slp_amount.fixed_div_floor(&e, &current_slp_supply, &SCALAR_7);
-        let withdraw_value = total_value.fixed_mul_floor(&e, &withdraw_ratio, &SCALAR_7);
-        log!(&e, "withdraw_ratio: {} withdraw_value: {}", withdraw_ratio, withdraw_value);
+        let slp_token = storage::get_slp_token(&e);
+        let slp_token_client = token::Client::new(&e, &slp_token);
+        slp_token_client.burn(&user, &slp_amount);
 
-        if withdraw_value == 0 || withdraw_value > total_value {
-            panic_with_error!(&e, PoolError::InsufficientFundsForWithdrawal);
+        // Transfer tokens to the user
+        for (token_info, withdraw_amount) in tokens.iter().zip(quote.amounts.iter()) {
+            let token_client = token::Client::new(&e, &token_info.address);
+            token_client.transfer(&e.current_contract_address(), &user, &withdraw_amount);
         }
 
-        let a_ratio = a_value.fixed_div_floor(&e, &total_value, &SCALAR_7);
-        let b_ratio = SCALAR_7 - a_ratio;
-
-        log!(&e, "a_ratio: {} b_ratio: {}", a_ratio, b_ratio);
-
-        // Calculate the USD value to withdraw for each token
-        let (withdraw_a_value, withdraw_b_value) = if a_ratio == token_a_info.target_ratio as i128 {
-            // If the pool is already at the target ratio, withdraw the same amount of each token
-            let withdraw_a_value = withdraw_value.fixed_mul_floor(&e, &a_ratio, &SCALAR_7);
-            (withdraw_a_value, withdraw_value - withdraw_a_value)
-        } else if a_ratio > token_a_info.target_ratio as i128 {
-            // If the pool is above the target ratio, withdraw more of token A
-            let excess_a_ratio = a_ratio - token_a_info.target_ratio as i128;
-            let adjust_factor = excess_a_ratio.fixed_div_floor(&e, &a_ratio, &SCALAR_7);
-            let withdraw_a = withdraw_value.fixed_mul_floor(&e, &a_ratio, &SCALAR_7).fixed_mul_floor(&e, &(SCALAR_7 + adjust_factor), &SCALAR_7);
-            log!(&e, "excess_a_ratio: {} adjust_factor: {} withdraw_a: {}", excess_a_ratio, adjust_factor, withdraw_a);
-            (withdraw_a, withdraw_value - withdraw_a)
-        } else {
-            // If the pool is below the target ratio, withdraw more of token B
-            let excess_b_ratio = token_a_info.target_ratio as i128 - a_ratio;
-            let adjust_factor = excess_b_ratio.fixed_div_floor(&e, &b_ratio, &SCALAR_7);
-            let withdraw_b = withdraw_value.fixed_mul_floor(&e, &b_ratio, &SCALAR_7).fixed_mul_floor(&e, &(SCALAR_7 + adjust_factor), &SCALAR_7);
-            log!(&e, "excess_b_ratio: {} adjust_factor: {} withdraw_b: {}", excess_b_ratio, adjust_factor, withdraw_b);
-            (withdraw_value - withdraw_b, withdraw_b)
-        };
+        events::withdraw(&e, user, slp_amount, quote.amounts.clone(), quote.fee_value, new_slp_supply);
+        quote.amounts
+    }
+
+    fn withdraw_single(e: Env, user: Address, slp_amount: i128, token: Address, min_amount_out: i128) -> i128 {
+        storage::extend_instance(&e);
+        user.require_auth();
+
+        if !storage::is_init(&e) {
+            panic_with_error!(&e, PoolError::NotInitialized);
+        }
 
-        log!(&e, "withdraw_a_value: {} withdraw_b_value: {}", withdraw_a_value, withdraw_b_value);
+        let mut tokens = storage::get_tokens(&e);
+        let index = pool::find_token(&e, &tokens, &token);
+        let prices = pool::load_prices(&e, &tokens, PriceMode::Twap, false);
+        let scalars = pool::get_scalars(&e, &tokens);
 
-        let withdraw_a_amount = withdraw_a_value.fixed_div_floor(&e, &token_a_price, &SCALAR_7);
-        let withdraw_b_amount = withdraw_b_value.fixed_div_floor(&e, &token_b_price, &SCALAR_7);
+        // Calculate current pool value
+        let values = pool::calculate_values(&e, &pool::get_supplies(&e, &tokens), &prices, &scalars);
+        let total_value = pool::sum(&values);
 
-        log!(&e, "withdraw_a_amount: {} withdraw_b_amount: {}", withdraw_a_amount, withdraw_b_amount);
+        // Calculate USD value to withdraw at the SLP price including traders' PnL
+        let withdraw_value = pool::calculate_withdraw_value(&e, slp_amount, pool::calculate_aum(&e, &tokens, &prices, &scalars, total_value));
 
-        // Check if there are sufficient funds for withdrawal
-        let token_a_client = token::Client::new(&e, &token_a_info.address);
-        let token_b_client = token::Client::new(&e, &token_b_info.address);
-        let available_a = token_a_client.balance(&e.current_contract_address());
-        let available_b = token_b_client.balance(&e.current_contract_address());
+        // The full value is paid out in the token, so the fee depends on how far that moves it from its target ratio
+        let mut token_info = tokens.get_unchecked(index);
+        let value = values.get_unchecked(index);
+        let fee_rate = pool::calculate_fee_rate(&e, value, value - withdraw_value, total_value, total_value - withdraw_value, token_info.target_ratio);
+        let fee_value = withdraw_value.fixed_mul_ceil(&e, &fee_rate, &SCALAR_7);
+        let withdraw_amount = pool::calculate_amount(&e, withdraw_value - fee_value, prices.get_unchecked(index), scalars.get_unchecked(index));
+
+        log!(&e, "token: {} fee_rate: {} withdraw_amount: {}", index, fee_rate, withdraw_amount);
+
+        if withdraw_amount < min_amount_out {
+            panic_with_error!(&e, PoolError::InsufficientWithdrawOut);
+        }
 
-        if withdraw_a_amount > available_a || withdraw_b_amount > available_b {
-            panic_with_error!(&e, PoolError::InsufficientFundsForWithdrawal);
+        // Tokens borrowed by the position manager can not be withdrawn
+        if withdraw_amount > pool::get_available_liquidity(&token_info) {
+            panic_with_error!(&e, PoolError::InsufficientLiquidity);
         }
 
-        // Update token supplies
-        token_a_info.total_supply = token_a_info.total_supply - withdraw_a_amount;
-        token_b_info.total_supply = token_b_info.total_supply - withdraw_b_amount;
-        storage::set_token_a_info(&e, &token_a_info);
-        storage::set_token_b_info(&e, &token_b_info);
+        // Update token supply
+        token_info.total_supply -= withdraw_amount;
+        tokens.set(index, token_info);
+        storage::set_tokens(&e, &tokens);
 
         // Burn SLP tokens
-        let new_slp_supply = current_slp_supply - slp_amount;
+        let new_slp_supply = storage::get_slp_supply(&e) - slp_amount;
         storage::set_slp_supply(&e, new_slp_supply);
 
         let slp_token = storage::get_slp_token(&e);
         let slp_token_client = token::Client::new(&e, &slp_token);
         slp_token_client.burn(&user, &slp_amount);
 
-        // Transfer tokens to the user
-        token_a_client.transfer(&e.current_contract_address(), &user, &withdraw_a_amount);
-        token_b_client.transfer(&e.current_contract_address(), &user, &withdraw_b_amount);
+        // Transfer the token to the user
+        let token_client = token::Client::new(&e, &token);
+        token_client.transfer(&e.current_contract_address(), &user, &withdraw_amount);
 
-        (withdraw_a_amount, withdraw_b_amount)
+        events::withdraw_single(&e, user, token, slp_amount, withdraw_amount, fee_value, new_slp_supply);
+        withdraw_amount
+    }
+
+    fn swap(e: Env, user: Address, token_in: Address, token_out: Address, amount_in: i128, min_amount_out: i128) -> i128 {
+        storage::extend_instance(&e);
+        user.require_auth();
+
+        if !storage::is_init(&e) {
+            panic_with_error!(&e, PoolError::NotInitialized);
+        }
+
+        if amount_in <= 0 {
+            panic_with_error!(&e, PoolError::InvalidAmount);
+        }
+
+        let mut tokens = storage::get_tokens(&e);
+        let index_in = pool::find_token(&e, &tokens, &token_in);
+        let index_out = pool::find_token(&e, &tokens, &token_out);
+        if index_in == index_out {
+            panic_with_error!(&e, PoolError::InvalidTokenAddress);
+        }
+        let prices = pool::load_swap_prices(&e, &tokens, index_in, index_out);
+        let scalars = pool::get_scalars(&e, &tokens);
+
+        // Calculate current pool value
+        let values = pool::calculate_values(&e, &pool::get_supplies(&e, &tokens), &prices, &scalars);
+        let total_value = pool::sum(&values);
+
+        // The swap executes at the worse of the spot price and the TWAP for the user
+        let price_in = prices.get_unchecked(index_in);
+        let price_out = prices.get_unchecked(index_out);
+
+        // The swap moves value from one token to the other, the pool value stays the same.
+        // The fee is the highest of the rates for the two legs, so a swap is only discounted
+        // when both tokens move towards their target ratio.
+        let mut token_info_in = tokens.get_unchecked(index_in);
+        let mut token_info_out = tokens.get_unchecked(index_out);
+        let swap_value = amount_in.fixed_mul_floor(&e, &price_in, &scalars.get_unchecked(index_in));
+        let value_in = values.get_unchecked(index_in);
+        let value_out = values.get_unchecked(index_out);
+        let fee_rate_in = pool::calculate_fee_rate(&e, value_in, value_in + swap_value, total_value, total_value, token_info_in.target_ratio);
+        let fee_rate_out = pool::calculate_fee_rate(&e, value_out, value_out - swap_value, total_value, total_value, token_info_out.target_ratio);
+        let fee_rate = fee_rate_in.max(fee_rate_out);
+        let fee_value = swap_value.fixed_mul_ceil(&e, &fee_rate, &SCALAR_7);
+        let amount_out = pool::calculate_amount(&e, swap_value - fee_value, price_out, scalars.get_unchecked(index_out));
+
+        log!(&e, "swap_value: {} fee_rate: {} amount_out: {}", swap_value, fee_rate, amount_out);
+
+        if amount_out < min_amount_out {
+            panic_with_error!(&e, PoolError::InsufficientAmountOut);
+        }
+
+        // Tokens borrowed by the position manager can not be swapped out
+        if amount_out > pool::get_available_liquidity(&token_info_out) {
+            panic_with_error!(&e, PoolError::InsufficientLiquidity);
+        }
+
+        // Update token supplies, the fee stays in the pool
+        token_info_in.total_supply += amount_in;
+        token_info_out.total_supply -= amount_out;
+        tokens.set(index_in, token_info_in);
+        tokens.set(index_out, token_info_out);
+        storage::set_tokens(&e, &tokens);
+
+        // Transfer the tokens
+        let token_in_client = token::Client::new(&e, &token_in);
+        token_in_client.transfer(&user, &e.current_contract_address(), &amount_in);
+        let token_out_client = token::Client::new(&e, &token_out);
+        token_out_client.transfer(&e.current_contract_address(), &user, &amount_out);
+
+        events::swap(&e, user, token_in, token_out, amount_in, amount_out, fee_value);
+        amount_out
+    }
+
+    fn quote_deposit(e: Env, amounts: Vec<i128>) -> DepositQuote {
+        storage::extend_instance(&e);
+        pool::calculate_deposit(&e, &storage::get_tokens(&e), &amounts)
+    }
+
+    fn quote_withdraw(e: Env, slp_amount: i128) -> WithdrawQuote {
+        storage::extend_instance(&e);
+        pool::calculate_withdraw(&e, &storage::get_tokens(&e), slp_amount)
     }
 
     fn borrow(e: Env, token: Address, amount: i128, fee: i128) {
@@ -354,78 +544,64 @@ impl Pool for PoolContract {
         position_manager.require_auth();
         position_manager.require_auth_for_args(vec![&e, token.clone().into_val(&e)]);
 
-        //TODO: This seems stupid
-        let token_a_info = storage::get_token_a_info(&e);
-        let mut token_info = token_a_info.clone();
-        if token != token_a_info.address {
-            token_info = storage::get_token_b_info(&e);
-            if token != token_info.address {
-                panic_with_error!(&e, PoolError::InvalidTokenAddress);
-            }
-        }
+        let mut tokens = storage::get_tokens(&e);
+        let index = pool::find_token(&e, &tokens, &token);
+        let mut token_info = tokens.get_unchecked(index);
 
         let token_client = token::Client::new(&e, &token);
-        let current_balance = token_client.balance(&e.current_contract_address());
 
         // Check if there's enough liquidity to borrow
-        if amount > current_balance {
+        if amount > pool::get_available_liquidity(&token_info) {
             panic_with_error!(&e, PoolError::InsufficientLiquidity);
         }
 
-        // Check if borrowing would leave less than 10% of total supply
-        let min_required_balance = token_info.total_supply / 10; //TODO: This does not require fixed point math?
-        if current_balance - amount < min_required_balance {
+        // Check if borrowing would push the utilization above the maximum
+        token_info.reserved += amount;
+        if pool::calculate_utilization(&e, &token_info) > storage::get_max_utilization(&e) {
             panic_with_error!(&e, PoolError::ExcessiveBorrowing);
         }
 
         // Transfer the borrowed amount to the position manager
         token_client.transfer(&e.current_contract_address(), &position_manager, &amount);
 
-        //TODO: Fix this with authorization for now fee is 0 so it's not needed
-        // Transfer the fee from the position manager to the pool
-        //token_client.transfer(&position_manager, &e.current_contract_address(), &fee);
+        // Transfer the fee from the position manager to the pool, authorized by the position manager
+        token_client.transfer(&position_manager, &e.current_contract_address(), &fee);
 
         // Update the token supply with the fee
         token_info.total_supply = token_info.total_supply + fee;
 
         // Update storage
-        if token == token_a_info.address {
-            storage::set_token_a_info(&e, &token_info);
-        } else {
-            storage::set_token_b_info(&e, &token_info);
-        }
+        tokens.set(index, token_info.clone());
+        storage::set_tokens(&e, &tokens);
+
+        events::borrow(&e, token, amount, fee, token_info);
     }
 
-    fn repay(e: Env, token: Address, amount: i128, fee: i128) {
+    fn repay(e: Env, token: Address, amount: i128, fee: i128, borrowed: i128) {
         storage::extend_instance(&e);
 
         let position_manager = storage::get_position_manager(&e);
         position_manager.require_auth();
 
-        let token_a_info = storage::get_token_a_info(&e);
-        let mut token_info = token_a_info.clone();
-
-        if token != token_a_info.address {
-            token_info = storage::get_token_b_info(&e);
-            if token != token_info.address {
-                panic_with_error!(&e, PoolError::InvalidTokenAddress);
-            }
-        }
+        let mut tokens = storage::get_tokens(&e);
+        let index = pool::find_token(&e, &tokens, &token);
+        let mut token_info = tokens.get_unchecked(index);
 
         let token_client = token::Client::new(&e, &token);
 
         // Transfer the repaid amount from the position manager to the pool
         token_client.transfer(&position_manager, &e.current_contract_address(), &(amount + fee));
 
-        // Update the token supply with the fee
-        token_info.total_supply = token_info.total_supply + fee;
+        // Update the token supply with the realized PnL and the fee and release the settled borrow.
+        // Traders in profit repay less than they borrowed, traders in loss more.
+        token_info.total_supply = token_info.total_supply + amount - borrowed + fee;
+        token_info.reserved -= borrowed;
 
         // Update storage
-        if token == token_a_info.address {
-            storage::set_token_a_info(&e, &token_info);
-        } else {
-            storage::set_token_b_info(&e, &token_info);
-        }
+        tokens.set(index, token_info.clone());
+        storage::set_tokens(&e, &tokens);
+
+        events::repay(&e, token, amount, fee, borrowed, token_info);
     }
 
     fn get_oracle(e: Env) -> Address {
@@ -440,17 +616,137 @@ impl Pool for PoolContract {
 
     fn get_token_info(e: Env, token: Address) -> TokenInfo {
         storage::extend_instance(&e);
-        let token_a_info = storage::get_token_a_info(&e);
-        if token == token_a_info.address {
-            return token_a_info;
+        let tokens = storage::get_tokens(&e);
+        tokens.get_unchecked(pool::find_token(&e, &tokens, &token))
+    }
+
+    fn get_tokens(e: Env) -> Vec<TokenInfo> {
+        storage::extend_instance(&e);
+        storage::get_tokens(&e)
+    }
+
+    fn get_utilization(e: Env, token: Address) -> i128 {
+        storage::extend_instance(&e);
+        let tokens = storage::get_tokens(&e);
+        pool::calculate_utilization(&e, &tokens.get_unchecked(pool::find_token(&e, &tokens, &token)))
+    }
+
+    fn get_max_utilization(e: Env) -> i128 {
+        storage::extend_instance(&e);
+        storage::get_max_utilization(&e)
+    }
+
+    fn get_price_config(e: Env, asset: Address) -> PriceConfig {
+        storage::extend_instance(&e);
+        storage::get_price_config(&e, &asset)
+    }
+
+    fn sync(e: Env) -> Vec<i128> {
+        storage::extend_instance(&e);
+        let mut gaps = Vec::new(&e);
+        for token_info in storage::get_tokens(&e).iter() {
+            gaps.push_back(pool::calculate_balance_gap(&e, &token_info));
         }
+        gaps
+    }
+
+    fn set_admin(e: Env, new_admin: Address) {
+        storage::extend_instance(&e);
+
+        let admin = storage::get_admin(&e);
+        admin.require_auth();
+
+        storage::set_proposed_admin(&e, &new_admin);
+        events::set_admin(&e, admin, new_admin);
+    }
 
-        let token_b_info = storage::get_token_b_info(&e);
-        if token == token_b_info.address {
-            return token_b_info;
+    fn accept_admin(e: Env) {
+        storage::extend_instance(&e);
+
+        let new_admin = match storage::get_proposed_admin(&e) {
+            Some(new_admin) => new_admin,
+            None => panic_with_error!(&e, PoolError::NoProposedAdmin),
+        };
+        new_admin.require_auth();
+
+        storage::set_admin(&e, &new_admin);
+        storage::remove_proposed_admin(&e);
+        events::accept_admin(&e, new_admin);
+    }
+
+    fn get_admin(e: Env) -> Address {
+        storage::extend_instance(&e);
+        storage::get_admin(&e)
+    }
+
+    fn set_oracle(e: Env, oracle: Address) {
+        storage::extend_instance(&e);
+
+        let admin = storage::get_admin(&e);
+        admin.require_auth();
+
+        storage::set_oracle(&e, &oracle);
+        storage::set_oracle_decimals(&e, oracle::load_decimals(&e, &oracle));
+        events::set_oracle(&e, admin, oracle);
+    }
+
+    fn set_position_manager(e: Env, position_manager: Address) {
+        storage::extend_instance(&e);
+
+        let admin = storage::get_admin(&e);
+        admin.require_auth();
+
+        storage::set_position_manager(&e, &position_manager);
+        events::set_position_manager(&e, admin, position_manager);
+    }
+
+    fn set_target_ratios(e: Env, ratios: Vec<u32>) {
+        storage::extend_instance(&e);
+
+        let admin = storage::get_admin(&e);
+        admin.require_auth();
+
+        let mut tokens = storage::get_tokens(&e);
+        if ratios.len() != tokens.len() {
+            panic_with_error!(&e, PoolError::InvalidTokenCount);
         }
+        pool::validate_target_ratios(&e, &ratios);
 
-        panic_with_error!(&e, PoolError::InvalidTokenAddress);
+        for (index, ratio) in ratios.iter().enumerate() {
+            let mut token_info = tokens.get_unchecked(index as u32);
+            token_info.target_ratio = ratio;
+            tokens.set(index as u32, token_info);
+        }
+        storage::set_tokens(&e, &tokens);
+
+        events::set_target_ratios(&e, admin, ratios);
     }
-}
 
+    fn set_max_utilization(e: Env, max_utilization: i128) {
+        storage::extend_instance(&e);
+
+        let admin = storage::get_admin(&e);
+        admin.require_auth();
+
+        if max_utilization <= 0 || max_utilization >= SCALAR_7 {
+            panic_with_error!(&e, PoolError::InvalidMaxUtilization);
+        }
+
+        storage::set_max_utilization(&e, max_utilization);
+        events::set_max_utilization(&e, admin, max_utilization);
+    }
+
+    fn set_price_config(e: Env, asset: Address, config: PriceConfig) {
+        storage::extend_instance(&e);
+
+        let admin = storage::get_admin(&e);
+        admin.require_auth();
+
+        if config.max_age == 0 || config.max_deviation <= 0 || config.twap_records == 0 || config.twap_records > MAX_TWAP_RECORDS {
+            panic_with_error!(&e, PoolError::InvalidPriceConfig);
+        }
+
+        storage::set_price_config(&e, &asset, &config);
+        events::set_price_config(&e, admin, asset, config);
+    }
+}
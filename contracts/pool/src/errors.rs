@@ -14,4 +14,14 @@ pub enum PoolError {
     StalePriceData = 507,
     InsufficientFundsForWithdrawal = 508,
     ExcessiveBorrowing = 509,
+    NoProposedAdmin = 510,
+    InvalidTokenCount = 511,
+    InvalidAmount = 512,
+    InsufficientAmountOut = 513,
+    InsufficientSlpOut = 514,
+    InsufficientWithdrawOut = 515,
+    InvalidMaxUtilization = 516,
+    PoolValueDepleted = 517,
+    PriceDeviation = 518,
+    InvalidPriceConfig = 519,
 }
\ No newline at end of file
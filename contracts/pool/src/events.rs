@@ -0,0 +1,131 @@
+use soroban_sdk::{symbol_short, Address, Env, Symbol, Vec};
+use crate::storage::{PriceConfig, TokenInfo};
+
+/// Tag and version of the event schema, the first topic of every event
+const EVENT_TAG: Symbol = symbol_short!("pool_v1");
+
+/// Emitted when the pool is initialized
+///
+/// - topics - `["pool_v1", "initialize", admin: Address]`
+/// - data - `[oracle: Address, position_manager: Address, slp: Address, tokens: Vec<TokenInfo>]`
+pub fn initialize(e: &Env, admin: Address, oracle: Address, position_manager: Address, slp: Address, tokens: Vec<TokenInfo>) {
+    let topics = (EVENT_TAG, Symbol::new(e, "initialize"), admin);
+    e.events().publish(topics, (oracle, position_manager, slp, tokens));
+}
+
+/// Emitted when a user deposits liquidity
+///
+/// - topics - `["pool_v1", "deposit", user: Address]`
+/// - data - `[amounts: Vec<i128>, slp_out: i128, fee_value: i128, slp_supply: i128]`
+pub fn deposit(e: &Env, user: Address, amounts: Vec<i128>, slp_out: i128, fee_value: i128, slp_supply: i128) {
+    let topics = (EVENT_TAG, Symbol::new(e, "deposit"), user);
+    e.events().publish(topics, (amounts, slp_out, fee_value, slp_supply));
+}
+
+/// Emitted when a user withdraws liquidity in every pool token
+///
+/// - topics - `["pool_v1", "withdraw", user: Address]`
+/// - data - `[slp_amount: i128, amounts: Vec<i128>, fee_value: i128, slp_supply: i128]`
+pub fn withdraw(e: &Env, user: Address, slp_amount: i128, amounts: Vec<i128>, fee_value: i128, slp_supply: i128) {
+    let topics = (EVENT_TAG, Symbol::new(e, "withdraw"), user);
+    e.events().publish(topics, (slp_amount, amounts, fee_value, slp_supply));
+}
+
+/// Emitted when a user withdraws liquidity in a single pool token
+///
+/// - topics - `["pool_v1", "withdraw_single", user: Address]`
+/// - data - `[token: Address, slp_amount: i128, amount: i128, fee_value: i128, slp_supply: i128]`
+pub fn withdraw_single(e: &Env, user: Address, token: Address, slp_amount: i128, amount: i128, fee_value: i128, slp_supply: i128) {
+    let topics = (EVENT_TAG, Symbol::new(e, "withdraw_single"), user);
+    e.events().publish(topics, (token, slp_amount, amount, fee_value, slp_supply));
+}
+
+/// Emitted when a user swaps one pool token for another
+///
+/// - topics - `["pool_v1", "swap", user: Address]`
+/// - data - `[token_in: Address, token_out: Address, amount_in: i128, amount_out: i128, fee_value: i128]`
+pub fn swap(e: &Env, user: Address, token_in: Address, token_out: Address, amount_in: i128, amount_out: i128, fee_value: i128) {
+    let topics = (EVENT_TAG, Symbol::new(e, "swap"), user);
+    e.events().publish(topics, (token_in, token_out, amount_in, amount_out, fee_value));
+}
+
+/// Emitted when the position manager borrows a token
+///
+/// - topics - `["pool_v1", "borrow", token: Address]`
+/// - data - `[amount: i128, fee: i128, token_info: TokenInfo]`
+pub fn borrow(e: &Env, token: Address, amount: i128, fee: i128, token_info: TokenInfo) {
+    let topics = (EVENT_TAG, Symbol::new(e, "borrow"), token);
+    e.events().publish(topics, (amount, fee, token_info));
+}
+
+/// Emitted when the position manager repays a token
+///
+/// - topics - `["pool_v1", "repay", token: Address]`
+/// - data - `[amount: i128, fee: i128, borrowed: i128, token_info: TokenInfo]`
+pub fn repay(e: &Env, token: Address, amount: i128, fee: i128, borrowed: i128, token_info: TokenInfo) {
+    let topics = (EVENT_TAG, Symbol::new(e, "repay"), token);
+    e.events().publish(topics, (amount, fee, borrowed, token_info));
+}
+
+/// Emitted when the admin proposes a new admin
+///
+/// - topics - `["pool_v1", "set_admin", admin: Address]`
+/// - data - `new_admin: Address`
+pub fn set_admin(e: &Env, admin: Address, new_admin: Address) {
+    let topics = (EVENT_TAG, Symbol::new(e, "set_admin"), admin);
+    e.events().publish(topics, new_admin);
+}
+
+/// Emitted when the proposed admin accepts the role
+///
+/// - topics - `["pool_v1", "accept_admin", new_admin: Address]`
+/// - data - `()`
+pub fn accept_admin(e: &Env, new_admin: Address) {
+    let topics = (EVENT_TAG, Symbol::new(e, "accept_admin"), new_admin);
+    e.events().publish(topics, ());
+}
+
+/// Emitted when the admin sets a new oracle
+///
+/// - topics - `["pool_v1", "set_oracle", admin: Address]`
+/// - data - `oracle: Address`
+pub fn set_oracle(e: &Env, admin: Address, oracle: Address) {
+    let topics = (EVENT_TAG, Symbol::new(e, "set_oracle"), admin);
+    e.events().publish(topics, oracle);
+}
+
+/// Emitted when the admin sets a new position manager
+///
+/// - topics - `["pool_v1", "set_position_manager", admin: Address]`
+/// - data - `position_manager: Address`
+pub fn set_position_manager(e: &Env, admin: Address, position_manager: Address) {
+    let topics = (EVENT_TAG, Symbol::new(e, "set_position_manager"), admin);
+    e.events().publish(topics, position_manager);
+}
+
+/// Emitted when the admin sets new target ratios
+///
+/// - topics - `["pool_v1", "set_target_ratios", admin: Address]`
+/// - data - `ratios: Vec<u32>`
+pub fn set_target_ratios(e: &Env, admin: Address, ratios: Vec<u32>) {
+    let topics = (EVENT_TAG, Symbol::new(e, "set_target_ratios"), admin);
+    e.events().publish(topics, ratios);
+}
+
+/// Emitted when the admin sets a new maximum utilization
+///
+/// - topics - `["pool_v1", "set_max_utilization", admin: Address]`
+/// - data - `max_utilization: i128`
+pub fn set_max_utilization(e: &Env, admin: Address, max_utilization: i128) {
+    let topics = (EVENT_TAG, Symbol::new(e, "set_max_utilization"), admin);
+    e.events().publish(topics, max_utilization);
+}
+
+/// Emitted when the admin sets the oracle limits of an asset
+///
+/// - topics - `["pool_v1", "set_price_config", admin: Address]`
+/// - data - `[asset: Address, max_age: u64, max_deviation: i128, twap_records: u32]`
+pub fn set_price_config(e: &Env, admin: Address, asset: Address, config: PriceConfig) {
+    let topics = (EVENT_TAG, Symbol::new(e, "set_price_config"), admin);
+    e.events().publish(topics, (asset, config.max_age, config.max_deviation, config.twap_records));
+}
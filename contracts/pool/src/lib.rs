@@ -7,5 +7,8 @@ mod contract;
 mod errors;
 mod constants;
 mod oracle;
+mod pool;
+mod events;
+mod position_manager;
 
 pub use contract::*;
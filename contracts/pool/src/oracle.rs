@@ -1,7 +1,22 @@
+use oracle_utils::{calculate_deviation, calculate_twap, normalize_price};
 use sep_40_oracle::{Asset, PriceFeedClient};
 use soroban_sdk::{Address, Env, panic_with_error};
 use soroban_sdk::unwrap::UnwrapOptimized;
 use crate::errors::PoolError;
+use crate::storage;
+
+/// How a price is derived from the oracle's records
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum PriceMode {
+    /// The latest price
+    Spot,
+    /// The time-weighted average of the asset's latest `twap_records` prices
+    Twap,
+    /// The lower of the spot price and the TWAP
+    Min,
+    /// The higher of the spot price and the TWAP
+    Max,
+}
 
 /// Load a price from the Pool's oracle without caching.
 ///
@@ -9,15 +24,50 @@ use crate::errors::PoolError;
 /// * e - The environment
 /// * oracle - The address of the oracle contract
 /// * asset - The address of the underlying asset
+/// * mode - How the price is derived from the oracle's records
+/// * check_deviation - Whether to guard against a price move from the previous record. Paths that
+///   only reduce exposure skip the guard so they keep working through genuine market moves.
+///
+/// ### Returns
+/// The price normalized to 7 decimals
 ///
 /// ### Panics
-/// If the price is stale
-pub(crate) fn load_price(e: &Env, oracle: Address, asset: Address) -> i128 {
+/// If the price is older than the asset's maximum age, or if `check_deviation` is set and the price
+/// moved more than its maximum deviation from the previous oracle record
+pub(crate) fn load_price(e: &Env, oracle: Address, asset: Address, mode: PriceMode, check_deviation: bool) -> i128 {
+    let config = storage::get_price_config(e, &asset);
     let oracle_client = PriceFeedClient::new(e, &oracle);
     let oracle_asset = Asset::Stellar(asset.clone());
     let price_data = oracle_client.lastprice(&oracle_asset).unwrap_optimized();
-    if price_data.timestamp + 24 * 60 * 60 < e.ledger().timestamp() {
+    if price_data.timestamp + config.max_age < e.ledger().timestamp() {
         panic_with_error!(e, PoolError::StalePriceData);
     }
-    price_data.price
-}
\ No newline at end of file
+
+    // The latest record is the first in the history, compare it to the one before
+    let history = if mode == PriceMode::Spot && !check_deviation {
+        None
+    } else {
+        let records = if mode == PriceMode::Spot { 2 } else { config.twap_records.max(2) };
+        oracle_client.prices(&oracle_asset, &records)
+    };
+    if check_deviation {
+        if let Some(previous) = history.as_ref().and_then(|history| history.get(1)) {
+            if previous.price > 0 && calculate_deviation(e, price_data.price, previous.price) > config.max_deviation {
+                panic_with_error!(e, PoolError::PriceDeviation);
+            }
+        }
+    }
+
+    let price = match mode {
+        PriceMode::Spot => price_data.price,
+        PriceMode::Twap => calculate_twap(&history, config.twap_records, price_data.price),
+        PriceMode::Min => price_data.price.min(calculate_twap(&history, config.twap_records, price_data.price)),
+        PriceMode::Max => price_data.price.max(calculate_twap(&history, config.twap_records, price_data.price)),
+    };
+    normalize_price(price, storage::get_oracle_decimals(e))
+}
+
+/// Load the number of decimals of an oracle's prices
+pub(crate) fn load_decimals(e: &Env, oracle: &Address) -> u32 {
+    PriceFeedClient::new(e, oracle).decimals()
+}
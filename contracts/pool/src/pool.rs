@@ -0,0 +1,351 @@
+use soroban_fixed_point_math::SorobanFixedPoint;
+use soroban_sdk::{log, token, Address, Env, Vec, panic_with_error};
+use soroban_sdk::unwrap::UnwrapOptimized;
+use crate::constants::{BASE_LIQUIDITY_FEE, SCALAR_7, TAX_LIQUIDITY_FEE};
+use crate::errors::PoolError;
+use crate::oracle;
+use crate::oracle::PriceMode;
+use crate::position_manager::PositionManagerClient;
+use crate::storage::{self, DepositQuote, TokenInfo, WithdrawQuote};
+
+/// Find the index of a token in the pool
+///
+/// ### Panics
+/// If the token is not listed in the pool
+pub(crate) fn find_token(e: &Env, tokens: &Vec<TokenInfo>, token: &Address) -> u32 {
+    match tokens.iter().position(|info| info.address == *token) {
+        Some(index) => index as u32,
+        None => panic_with_error!(e, PoolError::InvalidTokenAddress),
+    }
+}
+
+/// Validate that the target ratios of the pool tokens sum to SCALAR_7
+///
+/// ### Panics
+/// If the ratios do not sum to SCALAR_7
+pub(crate) fn validate_target_ratios(e: &Env, ratios: &Vec<u32>) {
+    let mut total: u32 = 0;
+    for ratio in ratios.iter() {
+        total = total.checked_add(ratio).unwrap_optimized();
+    }
+    if total != SCALAR_7 as u32 {
+        panic_with_error!(e, PoolError::InvalidTargetRatio);
+    }
+}
+
+/// Load the oracle price of every pool token, in listing order. Withdrawals skip the deviation guard,
+/// see `oracle::load_price`.
+pub(crate) fn load_prices(e: &Env, tokens: &Vec<TokenInfo>, mode: PriceMode, check_deviation: bool) -> Vec<i128> {
+    let oracle = storage::get_oracle(e);
+    let mut prices = Vec::new(e);
+    for info in tokens.iter() {
+        prices.push_back(oracle::load_price(e, oracle.clone(), info.address, mode, check_deviation));
+    }
+    prices
+}
+
+/// Load the oracle price of every pool token for a swap, in listing order. The token in is priced at
+/// the lower and the token out at the higher of the spot price and the TWAP, the other tokens at the
+/// TWAP, so the swap executes at the worse price for the user and each price is loaded once.
+pub(crate) fn load_swap_prices(e: &Env, tokens: &Vec<TokenInfo>, index_in: u32, index_out: u32) -> Vec<i128> {
+    let oracle = storage::get_oracle(e);
+    let mut prices = Vec::new(e);
+    for (index, info) in tokens.iter().enumerate() {
+        let mode = match index as u32 {
+            index if index == index_in => PriceMode::Min,
+            index if index == index_out => PriceMode::Max,
+            _ => PriceMode::Twap,
+        };
+        prices.push_back(oracle::load_price(e, oracle.clone(), info.address, mode, true));
+    }
+    prices
+}
+
+/// Fetch the total supply of every pool token, in listing order
+pub(crate) fn get_supplies(e: &Env, tokens: &Vec<TokenInfo>) -> Vec<i128> {
+    let mut supplies = Vec::new(e);
+    for info in tokens.iter() {
+        supplies.push_back(info.total_supply);
+    }
+    supplies
+}
+
+/// Fetch the scalar of every pool token, 10 to the power of its decimals, in listing order
+pub(crate) fn get_scalars(e: &Env, tokens: &Vec<TokenInfo>) -> Vec<i128> {
+    let mut scalars = Vec::new(e);
+    for info in tokens.iter() {
+        scalars.push_back(10i128.pow(storage::get_token_decimals(e, &info.address)));
+    }
+    scalars
+}
+
+/// Calculate the value of each amount at the matching price, scaled by SCALAR_7
+pub(crate) fn calculate_values(e: &Env, amounts: &Vec<i128>, prices: &Vec<i128>, scalars: &Vec<i128>) -> Vec<i128> {
+    let mut values = Vec::new(e);
+    for (index, amount) in amounts.iter().enumerate() {
+        values.push_back(amount.fixed_mul_floor(e, &prices.get_unchecked(index as u32), &scalars.get_unchecked(index as u32)));
+    }
+    values
+}
+
+/// Calculate the amount of a token worth `value` at `price`, in the token's own decimals
+pub(crate) fn calculate_amount(e: &Env, value: i128, price: i128, scalar: i128) -> i128 {
+    value.fixed_mul_floor(e, &scalar, &price)
+}
+
+/// Calculate the unrealized PnL of all open positions at the pool's prices, positive when traders
+/// are in profit. Each traded token's open interest is read from the position manager and valued
+/// against its entry notional in the quote token. Sizes are in the traded token's decimals and
+/// notionals in the quote token's decimals.
+pub(crate) fn calculate_global_pnl(e: &Env, tokens: &Vec<TokenInfo>, prices: &Vec<i128>, scalars: &Vec<i128>) -> i128 {
+    let position_manager = PositionManagerClient::new(e, &storage::get_position_manager(e));
+    let quote_index = find_token(e, tokens, &position_manager.get_quote_token());
+    let quote_price = prices.get_unchecked(quote_index);
+    let quote_scalar = scalars.get_unchecked(quote_index);
+
+    let mut pnl = 0;
+    for (index, token_info) in tokens.iter().enumerate() {
+        if index as u32 == quote_index {
+            continue;
+        }
+        let open_interest = position_manager.get_open_interest(&token_info.address);
+        let price = prices.get_unchecked(index as u32);
+        let scalar = scalars.get_unchecked(index as u32);
+        let long_pnl = open_interest.long_size.fixed_mul_floor(e, &price, &scalar) - open_interest.long_notional.fixed_mul_ceil(e, &quote_price, &quote_scalar);
+        let short_pnl = open_interest.short_notional.fixed_mul_floor(e, &quote_price, &quote_scalar) - open_interest.short_size.fixed_mul_ceil(e, &price, &scalar);
+        pnl += long_pnl + short_pnl;
+    }
+    pnl
+}
+
+/// Calculate the assets under management of the pool, the value of its tokens less the unrealized
+/// PnL owed to traders. Traders in loss add their PnL to the pool's value.
+pub(crate) fn calculate_aum(e: &Env, tokens: &Vec<TokenInfo>, prices: &Vec<i128>, scalars: &Vec<i128>, total_value: i128) -> i128 {
+    let global_pnl = calculate_global_pnl(e, tokens, prices, scalars);
+    log!(e, "total_value: {} global_pnl: {}", total_value, global_pnl);
+    (total_value - global_pnl).max(0)
+}
+
+/// Calculate the share of the total value held in each token.
+/// The last token receives the remainder so the ratios always sum to SCALAR_7.
+pub(crate) fn calculate_ratios(e: &Env, values: &Vec<i128>, total_value: i128) -> Vec<i128> {
+    let mut ratios = Vec::new(e);
+    let mut remaining = SCALAR_7;
+    for (index, value) in values.iter().enumerate() {
+        let ratio = if index as u32 == values.len() - 1 {
+            remaining
+        } else {
+            value.fixed_div_floor(e, &total_value, &SCALAR_7)
+        };
+        remaining -= ratio;
+        ratios.push_back(ratio);
+    }
+    ratios
+}
+
+/// Calculate the fee rate for moving a token's value from `value` to `next_value`, scaled by SCALAR_7.
+///
+/// Moves towards the token's target value earn a rebate on the base fee in proportion to how far
+/// off target the token was. Moves away from it pay a tax on top of the base fee in proportion to
+/// the average deviation from the target, capped at the full tax.
+///
+/// ### Arguments
+/// * `value` - The value of the token held by the pool before the move
+/// * `next_value` - The value of the token held by the pool after the move
+/// * `total_value` - The value of the pool before the move
+/// * `next_total_value` - The value of the pool after the move
+/// * `target_ratio` - The target ratio of the token
+pub(crate) fn calculate_fee_rate(e: &Env, value: i128, next_value: i128, total_value: i128, next_total_value: i128, target_ratio: u32) -> i128 {
+    let target_value = total_value.fixed_mul_floor(e, &(target_ratio as i128), &SCALAR_7);
+    let next_target_value = next_total_value.fixed_mul_floor(e, &(target_ratio as i128), &SCALAR_7);
+    let diff = (value - target_value).abs();
+    let next_diff = (next_value - next_target_value).abs();
+
+    if next_diff < diff {
+        // A token with no target value is entirely off target, the rebate covers the whole base fee
+        if target_value == 0 {
+            return 0;
+        }
+        let rebate = TAX_LIQUIDITY_FEE.fixed_mul_floor(e, &diff, &target_value);
+        return (BASE_LIQUIDITY_FEE - rebate).max(0);
+    }
+
+    if next_target_value == 0 {
+        return BASE_LIQUIDITY_FEE + TAX_LIQUIDITY_FEE;
+    }
+    let average_diff = ((diff + next_diff) / 2).min(next_target_value);
+    BASE_LIQUIDITY_FEE + TAX_LIQUIDITY_FEE.fixed_mul_ceil(e, &average_diff, &next_target_value)
+}
+
+/// Calculate the value of the pool a withdrawal of `slp_amount` SLP tokens is entitled to
+///
+/// ### Panics
+/// If the withdrawal is worth nothing or more than the pool
+pub(crate) fn calculate_withdraw_value(e: &Env, slp_amount: i128, total_value: i128) -> i128 {
+    let current_slp_supply = storage::get_slp_supply(e);
+    let withdraw_ratio = slp_amount.fixed_div_floor(e, &current_slp_supply, &SCALAR_7);
+    let withdraw_value = total_value.fixed_mul_floor(e, &withdraw_ratio, &SCALAR_7);
+    log!(e, "withdraw_ratio: {} withdraw_value: {}", withdraw_ratio, withdraw_value);
+
+    if withdraw_value == 0 || withdraw_value > total_value {
+        panic_with_error!(e, PoolError::InsufficientFundsForWithdrawal);
+    }
+    withdraw_value
+}
+
+/// Fetch the amount of a token the pool can pay out. Tokens reserved by the position manager
+/// are still counted in the total supply but are not held by the pool.
+pub(crate) fn get_available_liquidity(token_info: &TokenInfo) -> i128 {
+    token_info.total_supply - token_info.reserved
+}
+
+/// Calculate the gap between the pool's actual balance of a token and the balance its bookkeeping
+/// expects, the total supply less the amount reserved by the position manager. A positive gap means the
+/// pool holds tokens it does not account for, a negative gap means it accounts for tokens it does not hold.
+pub(crate) fn calculate_balance_gap(e: &Env, token_info: &TokenInfo) -> i128 {
+    let balance = token::Client::new(e, &token_info.address).balance(&e.current_contract_address());
+    balance - get_available_liquidity(token_info)
+}
+
+/// Calculate the share of a token's supply reserved by the position manager, scaled by SCALAR_7
+pub(crate) fn calculate_utilization(e: &Env, token_info: &TokenInfo) -> i128 {
+    if token_info.total_supply <= 0 {
+        return 0;
+    }
+    token_info.reserved.fixed_div_ceil(e, &token_info.total_supply, &SCALAR_7)
+}
+
+/// Calculate the SLP tokens minted and the fee charged for depositing `amounts` into the pool
+///
+/// ### Panics
+/// * If the number of amounts does not match the number of pool tokens
+/// * If the first deposit is not at the target ratios
+pub(crate) fn calculate_deposit(e: &Env, tokens: &Vec<TokenInfo>, amounts: &Vec<i128>) -> DepositQuote {
+    if amounts.len() != tokens.len() {
+        panic_with_error!(e, PoolError::InvalidTokenCount);
+    }
+    if amounts.iter().any(|amount| amount < 0) {
+        panic_with_error!(e, PoolError::InvalidAmount);
+    }
+
+    // SLP is minted and burned at the TWAP, so a single spiky price update can not be arbitraged
+    let prices = load_prices(e, tokens, PriceMode::Twap, true);
+    let scalars = get_scalars(e, tokens);
+
+    // Calculate current pool value
+    let values = calculate_values(e, &get_supplies(e, tokens), &prices, &scalars);
+    let total_value = sum(&values);
+
+    // Calculate deposit value
+    let deposit_values = calculate_values(e, amounts, &prices, &scalars);
+    let deposit_total_value = sum(&deposit_values);
+
+    log!(e, "total_value: {} deposit_total_value: {}", total_value, deposit_total_value);
+
+    if total_value == 0 {
+        // First deposit, the deposit itself must be at the target ratios
+        let ratios = calculate_ratios(e, &deposit_values, deposit_total_value);
+        for (ratio, token_info) in ratios.iter().zip(tokens.iter()) {
+            if ratio != token_info.target_ratio as i128 {
+                panic_with_error!(e, PoolError::DepositDoesNotImproveRatio);
+            }
+        }
+    }
+
+    // Charge each deposited token a fee depending on how it moves the token relative to its target ratio.
+    // The first deposit is free as there are no other liquidity providers to receive the fee.
+    let mut fee_value = 0;
+    let mut next_values = Vec::new(e);
+    for (index, token_info) in tokens.iter().enumerate() {
+        let value = values.get_unchecked(index as u32);
+        let deposit_value = deposit_values.get_unchecked(index as u32);
+        if total_value != 0 && deposit_value > 0 {
+            let fee_rate = calculate_fee_rate(e, value, value + deposit_value, total_value, total_value + deposit_total_value, token_info.target_ratio);
+            log!(e, "token: {} fee_rate: {}", index as u32, fee_rate);
+            fee_value += deposit_value.fixed_mul_ceil(e, &fee_rate, &SCALAR_7);
+        }
+        next_values.push_back(value + deposit_value);
+    }
+
+    // Mint SLP tokens for the deposit value net of fees
+    let mint_value = deposit_total_value - fee_value;
+    let current_slp_supply = storage::get_slp_supply(e);
+    let slp_out = if current_slp_supply == 0 {
+        // If it's the first deposit, mint mint_value of SLP
+        mint_value
+    } else {
+        // Calculate proportional amount of SLP to mint at the SLP price including traders' PnL
+        let aum = calculate_aum(e, tokens, &prices, &scalars, total_value);
+        if aum == 0 {
+            panic_with_error!(e, PoolError::PoolValueDepleted);
+        }
+        mint_value.fixed_mul_floor(e, &current_slp_supply, &SCALAR_7).fixed_div_floor(e, &aum, &SCALAR_7)
+    };
+
+    DepositQuote {
+        slp_out,
+        fee_value,
+        ratios: calculate_ratios(e, &next_values, total_value + deposit_total_value),
+    }
+}
+
+/// Calculate the amount of each pool token paid out and the fee charged for burning `slp_amount` SLP tokens
+///
+/// ### Panics
+/// * If there are insufficient funds in the pool for the withdrawal
+pub(crate) fn calculate_withdraw(e: &Env, tokens: &Vec<TokenInfo>, slp_amount: i128) -> WithdrawQuote {
+    let prices = load_prices(e, tokens, PriceMode::Twap, false);
+    let scalars = get_scalars(e, tokens);
+
+    // Calculate current pool value
+    let values = calculate_values(e, &get_supplies(e, tokens), &prices, &scalars);
+    let total_value = sum(&values);
+
+    // Calculate USD value to withdraw at the SLP price including traders' PnL
+    let withdraw_value = calculate_withdraw_value(e, slp_amount, calculate_aum(e, tokens, &prices, &scalars, total_value));
+
+    // Skew the withdrawal towards the tokens above their target ratio. Each token is withdrawn
+    // in proportion to its ratio plus its excess over the target, so tokens below their target
+    // are withdrawn less and a pool at its target ratios is withdrawn pro rata.
+    let ratios = calculate_ratios(e, &values, total_value);
+    let mut weights = Vec::new(e);
+    for (ratio, token_info) in ratios.iter().zip(tokens.iter()) {
+        weights.push_back((2 * ratio - token_info.target_ratio as i128).max(0));
+    }
+    let total_weight = sum(&weights);
+
+    let mut amounts = Vec::new(e);
+    let mut total_fee_value = 0;
+    let mut next_values = Vec::new(e);
+    for (index, token_info) in tokens.iter().enumerate() {
+        let value = values.get_unchecked(index as u32);
+        let withdraw_token_value = withdraw_value.fixed_mul_floor(e, &weights.get_unchecked(index as u32), &total_weight);
+
+        // The fee stays in the pool, depending on how the withdrawal moves the token relative to its target ratio
+        let fee_rate = calculate_fee_rate(e, value, value - withdraw_token_value, total_value, total_value - withdraw_value, token_info.target_ratio);
+        let fee_value = withdraw_token_value.fixed_mul_ceil(e, &fee_rate, &SCALAR_7);
+        let withdraw_amount = calculate_amount(e, withdraw_token_value - fee_value, prices.get_unchecked(index as u32), scalars.get_unchecked(index as u32));
+
+        log!(e, "token: {} withdraw_value: {} fee_rate: {} withdraw_amount: {}", index as u32, withdraw_token_value, fee_rate, withdraw_amount);
+
+        // Check if there are sufficient funds for withdrawal
+        if withdraw_amount > get_available_liquidity(&token_info) {
+            panic_with_error!(e, PoolError::InsufficientFundsForWithdrawal);
+        }
+
+        amounts.push_back(withdraw_amount);
+        total_fee_value += fee_value;
+        next_values.push_back(value - withdraw_token_value + fee_value);
+    }
+
+    WithdrawQuote {
+        amounts,
+        fee_value: total_fee_value,
+        ratios: calculate_ratios(e, &next_values, sum(&next_values)),
+    }
+}
+
+/// Sum a list of values
+pub(crate) fn sum(values: &Vec<i128>) -> i128 {
+    values.iter().sum()
+}
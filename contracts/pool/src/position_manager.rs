@@ -0,0 +1,24 @@
+use soroban_sdk::{contractclient, contracttype, Address, Env};
+
+/// The open longs and shorts of a traded token, their size in the traded token and their entry
+/// notional, the size times the entry price, in the quote token
+#[derive(Clone)]
+#[contracttype]
+pub struct OpenInterest {
+    pub long_size: i128,
+    pub long_notional: i128,
+    pub short_size: i128,
+    pub short_notional: i128,
+}
+
+/// The part of the position manager interface used by the pool. Declared here instead of importing
+/// the position manager's wasm, which itself imports the pool.
+#[allow(dead_code)]
+#[contractclient(name = "PositionManagerClient")]
+pub trait PositionManager {
+    /// Retrieves the aggregate open interest of a traded token
+    fn get_open_interest(env: Env, token: Address) -> OpenInterest;
+
+    /// Retrieves the quote token positions are priced in
+    fn get_quote_token(env: Env) -> Address;
+}
@@ -1,5 +1,6 @@
-use soroban_sdk::{contracttype, Address, Env};
+use soroban_sdk::{contracttype, Address, Env, Vec};
 use soroban_sdk::unwrap::UnwrapOptimized;
+use crate::constants::{DECIMALS_7, DEFAULT_MAX_PRICE_AGE, DEFAULT_MAX_PRICE_DEVIATION, DEFAULT_TWAP_RECORDS, DEFAULT_MAX_UTILIZATION};
 
 const ONE_DAY_LEDGERS: u32 = 17280; // assumes 5s a ledger
 
@@ -10,12 +11,16 @@ const LEDGER_BUMP_INSTANCE: u32 = LEDGER_THRESHOLD_INSTANCE + ONE_DAY_LEDGERS; /
 #[contracttype]
 pub enum DataKey {
     Admin,
+    ProposedAdmin,
     Oracle,
     PositionManager,
     SlpToken,
     SlpSupply,
-    TokenA,
-    TokenB,
+    Tokens,
+    MaxUtilization,
+    PriceConfig(Address),
+    OracleDecimals,
+    TokenDecimals(Address),
 }
 
 #[derive(Clone)]
@@ -24,6 +29,31 @@ pub struct TokenInfo {
     pub address: Address,
     pub total_supply: i128,
     pub target_ratio: u32,
+    pub reserved: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PriceConfig {
+    pub max_age: u64,
+    pub max_deviation: i128,
+    pub twap_records: u32,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct DepositQuote {
+    pub slp_out: i128,
+    pub fee_value: i128,
+    pub ratios: Vec<i128>,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct WithdrawQuote {
+    pub amounts: Vec<i128>,
+    pub fee_value: i128,
+    pub ratios: Vec<i128>,
 }
 
 /// Bump the instance rent for the contract
@@ -51,6 +81,24 @@ pub fn set_admin(e: &Env, new_admin: &Address) {
     e.storage().instance().set(&DataKey::Admin, new_admin);
 }
 
+/// Fetch the proposed admin Address, if any
+pub fn get_proposed_admin(e: &Env) -> Option<Address> {
+    e.storage().instance().get(&DataKey::ProposedAdmin)
+}
+
+/// Set the proposed admin
+///
+/// ### Arguments
+/// * `proposed_admin` - The Address proposed as the next admin
+pub fn set_proposed_admin(e: &Env, proposed_admin: &Address) {
+    e.storage().instance().set(&DataKey::ProposedAdmin, proposed_admin);
+}
+
+/// Remove the proposed admin
+pub fn remove_proposed_admin(e: &Env) {
+    e.storage().instance().remove(&DataKey::ProposedAdmin);
+}
+
 /// Fetch the current oracle Address
 pub fn get_oracle(env: &Env) -> Address {
     env.storage().instance().get(&DataKey::Oracle).unwrap_optimized()
@@ -64,6 +112,36 @@ pub fn set_oracle(env: &Env, address: &Address) {
     env.storage().instance().set(&DataKey::Oracle, address);
 }
 
+/// Fetch the number of decimals of the oracle's prices
+pub fn get_oracle_decimals(env: &Env) -> u32 {
+    env.storage().instance().get(&DataKey::OracleDecimals).unwrap_or(DECIMALS_7)
+}
+
+/// Set the number of decimals of the oracle's prices
+///
+/// ### Arguments
+/// * `decimals` - The decimals reported by the oracle
+pub fn set_oracle_decimals(env: &Env, decimals: u32) {
+    env.storage().instance().set(&DataKey::OracleDecimals, &decimals);
+}
+
+/// Fetch the number of decimals of a pool token
+///
+/// ### Arguments
+/// * `token` - The Address of the token
+pub fn get_token_decimals(env: &Env, token: &Address) -> u32 {
+    env.storage().instance().get(&DataKey::TokenDecimals(token.clone())).unwrap_or(DECIMALS_7)
+}
+
+/// Set the number of decimals of a pool token
+///
+/// ### Arguments
+/// * `token` - The Address of the token
+/// * `decimals` - The decimals reported by the token
+pub fn set_token_decimals(env: &Env, token: &Address, decimals: u32) {
+    env.storage().instance().set(&DataKey::TokenDecimals(token.clone()), &decimals);
+}
+
 /// Fetch the current position manager Address
 pub fn get_position_manager(env: &Env) -> Address {
     env.storage().instance().get(&DataKey::PositionManager).unwrap_optimized()
@@ -103,28 +181,49 @@ pub fn set_slp_supply(env: &Env, supply: i128) {
     env.storage().instance().set(&DataKey::SlpSupply, &supply);
 }
 
-/// Fetch the token info for Token A
-pub fn get_token_a_info(env: &Env) -> TokenInfo {
-    env.storage().instance().get(&DataKey::TokenA).unwrap_optimized()
+/// Fetch the token info of every pool token, in listing order
+pub fn get_tokens(env: &Env) -> Vec<TokenInfo> {
+    env.storage().instance().get(&DataKey::Tokens).unwrap_optimized()
+}
+
+/// Set the token info of every pool token
+///
+/// ### Arguments
+/// * `tokens` - The TokenInfo of each pool token, in listing order
+pub fn set_tokens(env: &Env, tokens: &Vec<TokenInfo>) {
+    env.storage().instance().set(&DataKey::Tokens, tokens);
+}
+
+/// Fetch the maximum share of each token's supply that can be borrowed, scaled by SCALAR_7
+pub fn get_max_utilization(env: &Env) -> i128 {
+    env.storage().instance().get(&DataKey::MaxUtilization).unwrap_or(DEFAULT_MAX_UTILIZATION)
 }
 
-/// Set the token info for Token A
+/// Set the maximum share of each token's supply that can be borrowed
 ///
 /// ### Arguments
-/// * `info` - The TokenInfo to set
-pub fn set_token_a_info(env: &Env, info: &TokenInfo) {
-    env.storage().instance().set(&DataKey::TokenA, info);
+/// * `max_utilization` - The maximum utilization, scaled by SCALAR_7
+pub fn set_max_utilization(env: &Env, max_utilization: i128) {
+    env.storage().instance().set(&DataKey::MaxUtilization, &max_utilization);
 }
 
-/// Fetch the token info for Token B
-pub fn get_token_b_info(env: &Env) -> TokenInfo {
-    env.storage().instance().get(&DataKey::TokenB).unwrap_optimized()
+/// Fetch the oracle limits for an asset, defaulting to DEFAULT_MAX_PRICE_AGE and DEFAULT_MAX_PRICE_DEVIATION
+///
+/// ### Arguments
+/// * `asset` - The Address of the asset
+pub fn get_price_config(env: &Env, asset: &Address) -> PriceConfig {
+    env.storage().instance().get(&DataKey::PriceConfig(asset.clone())).unwrap_or(PriceConfig {
+        max_age: DEFAULT_MAX_PRICE_AGE,
+        max_deviation: DEFAULT_MAX_PRICE_DEVIATION,
+        twap_records: DEFAULT_TWAP_RECORDS,
+    })
 }
 
-/// Set the token info for Token B
+/// Set the oracle limits for an asset
 ///
 /// ### Arguments
-/// * `info` - The TokenInfo to set
-pub fn set_token_b_info(env: &Env, info: &TokenInfo) {
-    env.storage().instance().set(&DataKey::TokenB, info);
-}
\ No newline at end of file
+/// * `asset` - The Address of the asset
+/// * `config` - The PriceConfig to set
+pub fn set_price_config(env: &Env, asset: &Address, config: &PriceConfig) {
+    env.storage().instance().set(&DataKey::PriceConfig(asset.clone()), config);
+}
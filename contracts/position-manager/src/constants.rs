@@ -1,10 +1,26 @@
 /********** Numbers **********/
 /// Fixed-point scalar for 7 decimal numbers
 pub const SCALAR_7: i128 = 1_0000000;
+/// Number of decimals of SCALAR_7, prices and values are normalized to it
+pub const DECIMALS_7: u32 = 7;
+/// Fixed-point scalar for the cumulative borrow index
+pub const SCALAR_12: i128 = 1_000_000_000_000;
 
-pub const BASE_FEE: i128 = 6000; //
+/********** Default fees **********/
+pub const BASE_FEE: i128 = 6000;
 pub const HOURLY_BASE_FEE: i128 = 800;
 
 pub const IMPACT_FEE_SCALAR: i128 = 760_000_000_000_0000;
 
+/********** Oracle **********/
+/// Default maximum age of a price in seconds, strict because positions are levered
+pub const DEFAULT_MAX_PRICE_AGE: u64 = 10 * 60;
+/// Default maximum move of a price from the previous oracle record, scaled by SCALAR_7
+pub const DEFAULT_MAX_PRICE_DEVIATION: i128 = 0_5000000;
+/// Default number of oracle records averaged into a TWAP, a single record makes the TWAP the spot price
+pub const DEFAULT_TWAP_RECORDS: u32 = 1;
+/// Maximum number of oracle records averaged into a TWAP
+pub const MAX_TWAP_RECORDS: u32 = 20;
+
+pub const MIN_LEVERAGE: i128 = SCALAR_7;
 pub const MAX_LEVERAGE: i128 = 100 * SCALAR_7;
\ No newline at end of file
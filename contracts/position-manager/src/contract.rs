@@ -1,13 +1,13 @@
-use soroban_sdk::{contract, contractimpl, Address, Env, Symbol, contractclient, panic_with_error, IntoVal, vec, Vec, Val};
+use soroban_sdk::{contract, contractimpl, Address, Env, contractclient, panic_with_error, Vec};
 use soroban_sdk::token::TokenClient;
 use soroban_sdk::unwrap::UnwrapOptimized;
-use crate::constants::{MAX_LEVERAGE, SCALAR_7};
-use crate::{oracle, position, storage};
-use crate::storage::Position;
+use crate::constants::{MAX_LEVERAGE, MAX_TWAP_RECORDS, MIN_LEVERAGE, SCALAR_7};
+use crate::{events, oracle, position, storage};
+use crate::storage::{ClosePositionQuote, DecreaseAmount, FeeConfig, KeeperConfig, LiquidationQuote, OpenInterest, OpenPositionQuote, Position, PositionLimits, PriceConfig, Side};
 use crate::dependencies::pool::Client as PoolClient;
 use crate::errors::PositionManagerError;
+use crate::oracle::{PriceContext, PriceMode};
 use soroban_fixed_point_math::{SorobanFixedPoint};
-use soroban_sdk::auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation};
 
 #[contract]
 pub struct PositionManagerContract;
@@ -17,9 +17,74 @@ pub trait PositionManager {
     /// Initializes the position manager contract
     ///
     /// # Arguments
-    /// * `pool_contract` - The pool contract address (also serves as admin)
+    /// * `admin` - The admin address
+    /// * `pool_contract` - The pool contract address
     /// * `oracle` - The oracle contract address
-    fn initialize(env: Env, pool_contract: Address, oracle: Address, token_a: Address, token_b: Address);
+    /// * `quote_token` - The pool token prices are quoted in and shorts are collateralized in
+    fn initialize(env: Env, admin: Address, pool_contract: Address, oracle: Address, quote_token: Address);
+
+    /// Sets the position limits for a collateral token (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `token` - The address of the collateral token
+    /// * `limits` - The minimum and maximum leverage, minimum collateral and minimum notional size
+    ///
+    /// # Panics
+    /// * If the limits are inconsistent or exceed the protocol's maximum leverage
+    fn set_position_limits(env: Env, token: Address, limits: PositionLimits);
+
+    /// Retrieves the position limits for a collateral token
+    ///
+    /// # Arguments
+    /// * `token` - The address of the collateral token
+    fn get_position_limits(env: Env, token: Address) -> PositionLimits;
+
+    /// Sets the keeper rewards for a collateral token (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `token` - The address of the collateral token
+    /// * `config` - The share of execution and liquidation fees paid to keepers, scaled by SCALAR_7,
+    ///   and the minimum keeper fee reserved when an order is placed
+    ///
+    /// # Panics
+    /// * If a share is not between 0 and 1 or the minimum keeper fee is negative
+    fn set_keeper_config(env: Env, token: Address, config: KeeperConfig);
+
+    /// Retrieves the keeper rewards for a collateral token
+    ///
+    /// # Arguments
+    /// * `token` - The address of the collateral token
+    fn get_keeper_config(env: Env, token: Address) -> KeeperConfig;
+
+    /// Sets the fee parameters (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `config` - The base and hourly fees, scaled by SCALAR_7, and the impact fee scalar
+    ///
+    /// # Panics
+    /// * If a fee is negative or not below 1, or the impact fee scalar is not positive
+    fn set_fee_config(env: Env, config: FeeConfig);
+
+    /// Retrieves the fee parameters
+    fn get_fee_config(env: Env) -> FeeConfig;
+
+    /// Sets the oracle limits for an asset (only callable by the admin)
+    ///
+    /// # Arguments
+    /// * `asset` - The address of the asset
+    /// * `config` - The maximum age of the asset's price in seconds, its maximum move from the previous
+    ///   oracle record, scaled by SCALAR_7, and the number of oracle records averaged into its TWAP
+    ///
+    /// # Panics
+    /// * If the maximum age or the maximum deviation is not above 0
+    /// * If the number of TWAP records is 0 or above MAX_TWAP_RECORDS
+    fn set_price_config(env: Env, asset: Address, config: PriceConfig);
+
+    /// Retrieves the oracle limits for an asset
+    ///
+    /// # Arguments
+    /// * `asset` - The address of the asset
+    fn get_price_config(env: Env, asset: Address) -> PriceConfig;
 
     /// Opens a new position for a user
     ///
@@ -27,8 +92,16 @@ pub trait PositionManager {
     /// * `user` - The address of the user opening the position
     /// * `collateral` - The amount of collateral to deposit
     /// * `size` - The size of the position
-    /// * `token` - The address of the token to borrow
-    fn open_position(env: Env, user: Address, collateral: i128, size: u32, token: Address) -> i128;
+    /// * `token` - The address of the token to trade
+    /// * `side` - Whether the position is long or short `token`
+    /// * `acceptable_price` - The highest entry price for a long, or the lowest entry price for a short
+    ///
+    /// # Returns
+    /// A tuple containing the id of the new position and the fee charged
+    ///
+    /// # Panics
+    /// * If the entry price is worse than `acceptable_price`
+    fn open_position(env: Env, user: Address, collateral: i128, size: u32, token: Address, side: Side, acceptable_price: i128) -> (u32, i128);
 
     /// Open a new limit position for a user
     ///
@@ -36,45 +109,219 @@ pub trait PositionManager {
     /// * `user` - The address of the user opening the position
     /// * `collateral` - The amount of collateral to deposit
     /// * `size` - The size of the position
-    /// * `token` - The address of the token to borrow
+    /// * `token` - The address of the token to trade
+    /// * `side` - Whether the position is long or short `token`
     /// * `entry_price` - The price at which the position should be opened
-    fn open_limit_position(env: Env, user: Address, collateral: i128, size: u32, token: Address, entry_price: i128) -> i128;
+    ///
+    /// # Returns
+    /// A tuple containing the id of the new position and the fee charged
+    ///
+    /// # Panics
+    /// * If the entry price is not above 0
+    fn open_limit_position(env: Env, user: Address, collateral: i128, size: u32, token: Address, side: Side, entry_price: i128) -> (u32, i128);
 
-    fn fill_position(env: Env, user: Address, fee_taker: Address);
+    /// Cancels a pending limit position, refunding the collateral and the prepaid fee
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user cancelling the position
+    /// * `id` - The id of the limit position to cancel
+    ///
+    /// # Returns
+    /// The amount refunded to the user
+    ///
+    /// # Panics
+    /// * If the position has already been filled
+    fn cancel_limit_position(env: Env, user: Address, id: u32) -> i128;
 
-    fn add_stop_loss(env: Env, user: Address, stop_loss: i128);
+    /// Fills a limit position or executes a triggered take profit or stop loss
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user owning the position
+    /// * `id` - The id of the position
+    /// * `fee_taker` - The keeper receiving its share of the fee, and the reserved keeper fee when a take
+    ///   profit or stop loss is executed. A filled limit order keeps its reserve.
+    fn fill_position(env: Env, user: Address, id: u32, fee_taker: Address);
+
+    /// Sets the stop loss of a position, reserving the minimum keeper fee
+    fn add_stop_loss(env: Env, user: Address, id: u32, stop_loss: i128);
 
-    fn add_take_profit(env: Env, user: Address, take_profit: i128);
+    /// Sets the take profit of a position, reserving the minimum keeper fee
+    fn add_take_profit(env: Env, user: Address, id: u32, take_profit: i128);
 
     /// Closes an existing position for a user
     ///
     /// # Arguments
     /// * `user` - The address of the user closing the position
-    fn close_position(env: Env, user: Address) -> (i128, i128);
+    /// * `id` - The id of the position to close
+    /// * `min_payout` - The minimum amount to pay out to the user
+    ///
+    /// # Returns
+    /// A tuple containing the amount paid out to the user and the fee charged
+    ///
+    /// # Panics
+    /// * If the position is not filled
+    /// * If the payout is below `min_payout`
+    fn close_position(env: Env, user: Address, id: u32, min_payout: i128) -> (i128, i128);
+
+    /// Closes part of an existing position for a user, realizing its share of the PnL and fees
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user decreasing the position
+    /// * `id` - The id of the position to decrease
+    /// * `amount` - The part of the position to close, a fraction of it scaled by SCALAR_7 or an amount
+    ///   of its collateral. The borrowed amount is closed in the same proportion.
+    ///
+    /// # Returns
+    /// A tuple containing the amount paid out to the user and the fee charged
+    ///
+    /// # Panics
+    /// * If the position is not filled
+    /// * If the fraction is not between 0 and 1 (exclusive), or the collateral is not above 0 and below
+    ///   the position's collateral
+    /// * If the part closed does not cover its share of the debt and fees
+    /// * If the rest of the position is outside of the position limits
+    fn decrease_position(env: Env, user: Address, id: u32, amount: DecreaseAmount) -> (i128, i128);
+
+    /// Adds collateral and exposure to an existing position for a user
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user increasing the position
+    /// * `id` - The id of the position to increase
+    /// * `collateral` - The amount of collateral to add
+    /// * `leverage` - The leverage applied to the added collateral
+    /// * `acceptable_price` - The highest price to add to a long at, or the lowest price to add to a short at
+    ///
+    /// # Returns
+    /// The fee charged, including the borrow fee accrued on the existing position
+    ///
+    /// # Panics
+    /// * If the position is not filled
+    /// * If the collateral is not above 0 or the leverage is below the minimum leverage
+    /// * If the current price is worse than `acceptable_price`
+    /// * If the merged position is outside of the position limits
+    fn increase_position(env: Env, user: Address, id: u32, collateral: i128, leverage: u32, acceptable_price: i128) -> i128;
+
+    /// Adds collateral to an existing position, lowering its leverage
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user owning the position
+    /// * `id` - The id of the position
+    /// * `amount` - The amount of collateral to add
+    ///
+    /// # Panics
+    /// * If the position is not filled
+    fn add_collateral(env: Env, user: Address, id: u32, amount: i128);
+
+    /// Removes collateral from an existing position, raising its leverage
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user owning the position
+    /// * `id` - The id of the position
+    /// * `amount` - The amount of collateral to remove
+    ///
+    /// # Panics
+    /// * If the position is not filled
+    /// * If the position would be liquidatable after the removal
+    fn remove_collateral(env: Env, user: Address, id: u32, amount: i128);
 
     /// Liquidates a user's position if it meets liquidation criteria
     ///
     /// # Arguments
     /// * `user` - The address of the user whose position is being liquidated
-    /// * `liquidator` - The address of the account performing the liquidation //Not needed for MVP
-    fn liquidate(env: Env, user: Address, liquidator: Address);
+    /// * `id` - The id of the position to liquidate
+    /// * `liquidator` - The address of the account performing the liquidation, rewarded with the reserved
+    ///   keeper fee and its share of the liquidation fee
+    fn liquidate(env: Env, user: Address, id: u32, liquidator: Address);
+
+    /// Retrieves a position for a user
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user
+    /// * `id` - The id of the position
+    ///
+    /// # Returns
+    /// The user's position
+    ///
+    /// # Panics
+    /// If the user has no position with the given id
+    fn get_position(env: Env, user: Address, id: u32) -> Position;
 
-    /// Retrieves the current position for a user
+    /// Retrieves all open and pending positions for a user
     ///
     /// # Arguments
     /// * `user` - The address of the user
     ///
     /// # Returns
-    /// The user's current position
+    /// The user's positions, ordered by id
+    fn get_positions(env: Env, user: Address) -> Vec<Position>;
+
+    /// Retrieves the aggregate open interest of a traded token
+    ///
+    /// # Arguments
+    /// * `token` - The address of the traded token
+    ///
+    /// # Returns
+    /// The long and short size in the traded token and their entry notional in the quote token
+    fn get_open_interest(env: Env, token: Address) -> OpenInterest;
+
+    /// Retrieves the quote token positions are priced in
+    fn get_quote_token(env: Env) -> Address;
+
+    /// Retrieves the unrealized PnL of all open positions at the oracle TWAP
+    ///
+    /// # Returns
+    /// The PnL of traders, scaled by SCALAR_7. Positive when traders are in profit and the pool owes them,
+    /// negative when traders are in loss.
+    fn get_global_pnl(env: Env) -> i128;
+
+    /// Previews opening a market position without moving any tokens
+    ///
+    /// # Arguments
+    /// * `collateral` - The amount of collateral to deposit
+    /// * `size` - The size of the position
+    /// * `token` - The address of the token to trade
+    /// * `side` - Whether the position is long or short `token`
+    ///
+    /// # Returns
+    /// The entry price, the amount borrowed, the fee charged and the liquidation price of the position
+    ///
+    /// # Panics
+    /// * If the token can not be traded
+    /// * If the position is outside of the configured limits
+    fn quote_open_position(env: Env, collateral: i128, size: u32, token: Address, side: Side) -> OpenPositionQuote;
+
+    /// Previews the user closing a position without moving any tokens
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user owning the position
+    /// * `id` - The id of the position
+    ///
+    /// # Returns
+    /// The current price, the amount repaid to the pool, the fee charged and the payout to the user
     ///
     /// # Panics
-    /// If the user has no open position
-    fn get_position(env: Env, user: Address) -> Position;
+    /// If the user has no position with the given id
+    fn quote_close_position(env: Env, user: Address, id: u32) -> ClosePositionQuote;
+
+    /// Previews liquidating a position without moving any tokens
+    ///
+    /// # Arguments
+    /// * `user` - The address of the user owning the position
+    /// * `id` - The id of the position
+    ///
+    /// # Returns
+    /// Whether the position can be liquidated, the current and liquidation prices, the amount repaid
+    /// to the pool, the liquidation fee kept by the pool and the liquidator's reward
+    ///
+    /// # Panics
+    /// * If the user has no position with the given id
+    /// * If the position has not been filled
+    fn quote_liquidation(env: Env, user: Address, id: u32) -> LiquidationQuote;
 }
 
 #[contractimpl]
 impl PositionManager for PositionManagerContract {
-    fn initialize(env: Env, pool_contract: Address, oracle: Address, token_a: Address, token_b: Address) {
+    fn initialize(env: Env, admin: Address, pool_contract: Address, oracle: Address, quote_token: Address) {
         storage::extend_instance(&env);
 
         if storage::is_init(&env) {
@@ -82,282 +329,644 @@ impl PositionManager for PositionManagerContract {
         }
 
         storage::set_oracle(&env, &oracle);
-        storage::set_token_a(&env, &token_a);
-        storage::set_token_b(&env, &token_b);
+        storage::set_oracle_decimals(&env, oracle::load_decimals(&env, &oracle));
+        storage::set_quote_token(&env, &quote_token);
         storage::set_pool_contract(&env, &pool_contract);
+        storage::set_admin(&env, &admin);
+
+        events::initialize(&env, admin, pool_contract, oracle, quote_token);
     }
 
-    fn open_position(env: Env, user: Address, input: i128, size: u32, token: Address) -> i128 {
+    fn set_position_limits(env: Env, token: Address, limits: PositionLimits) {
         storage::extend_instance(&env);
 
-        //User must authenticate the opening of a position
-        user.require_auth();
+        let admin = storage::get_admin(&env);
+        admin.require_auth();
 
-        // Only 1 position per user
-        if storage::has_position(&env, &user) {
-            panic_with_error!(&env, PositionManagerError::PositionAlreadyExists);
+        if limits.min_leverage < MIN_LEVERAGE as u32
+            || limits.max_leverage > MAX_LEVERAGE as u32
+            || limits.min_leverage > limits.max_leverage
+            || limits.min_collateral <= 0
+            || limits.min_notional < 0 {
+            panic_with_error!(&env, PositionManagerError::InvalidInput);
         }
 
-        // Create the position
-        let oracle = storage::get_oracle(&env);
-        let entry_price = oracle::load_relative_price(&env, oracle.clone(), token.clone());
+        storage::set_position_limits(&env, &token, &limits);
+        events::set_position_limits(&env, admin, token, limits);
+    }
 
-        let to_borrow = input.fixed_mul_floor(&env, &(size as i128), &SCALAR_7);
-        let fee = position::calculate_impact_fee(&env, to_borrow, entry_price);
-        let position = Position {
-            filled: true,
-            token: token.clone(),
-            stop_loss: 0,
-            take_profit: 0,
-            entry_price,
-            borrowed: to_borrow,
-            leverage: size,
-            collateral: input,
-            timestamp: env.ledger().timestamp(),
-        };
+    fn get_position_limits(env: Env, token: Address) -> PositionLimits {
+        storage::extend_instance(&env);
+        storage::get_position_limits(&env, &token)
+    }
+
+    fn set_keeper_config(env: Env, token: Address, config: KeeperConfig) {
+        storage::extend_instance(&env);
+
+        let admin = storage::get_admin(&env);
+        admin.require_auth();
+
+        if config.fill_share < 0
+            || config.fill_share > SCALAR_7
+            || config.liquidation_share < 0
+            || config.liquidation_share > SCALAR_7
+            || config.min_keeper_fee < 0 {
+            panic_with_error!(&env, PositionManagerError::InvalidInput);
+        }
+
+        storage::set_keeper_config(&env, &token, &config);
+        events::set_keeper_config(&env, admin, token, config);
+    }
+
+    fn get_keeper_config(env: Env, token: Address) -> KeeperConfig {
+        storage::extend_instance(&env);
+        storage::get_keeper_config(&env, &token)
+    }
+
+    fn set_fee_config(env: Env, config: FeeConfig) {
+        storage::extend_instance(&env);
+
+        let admin = storage::get_admin(&env);
+        admin.require_auth();
+
+        if config.base_fee < 0
+            || config.base_fee >= SCALAR_7
+            || config.hourly_base_fee < 0
+            || config.hourly_base_fee >= SCALAR_7
+            || config.impact_fee_scalar <= 0 {
+            panic_with_error!(&env, PositionManagerError::InvalidInput);
+        }
+
+        storage::set_fee_config(&env, &config);
+
+        // Accrue the borrow indexes at the old hourly rate, they grow at the new one from now on
+        let pool_client = PoolClient::new(&env, &storage::get_pool_contract(&env));
+        for token_info in pool_client.get_tokens().iter() {
+            position::update_borrow_index(&env, &token_info.address);
+        }
+        events::set_fee_config(&env, admin, config);
+    }
+
+    fn get_fee_config(env: Env) -> FeeConfig {
+        storage::extend_instance(&env);
+        storage::get_fee_config(&env)
+    }
+
+    fn set_price_config(env: Env, asset: Address, config: PriceConfig) {
+        storage::extend_instance(&env);
+
+        let admin = storage::get_admin(&env);
+        admin.require_auth();
+
+        if config.max_age == 0 || config.max_deviation <= 0 || config.twap_records == 0 || config.twap_records > MAX_TWAP_RECORDS {
+            panic_with_error!(&env, PositionManagerError::InvalidInput);
+        }
+
+        storage::set_price_config(&env, &asset, &config);
+        events::set_price_config(&env, admin, asset, config);
+    }
+
+    fn get_price_config(env: Env, asset: Address) -> PriceConfig {
+        storage::extend_instance(&env);
+        storage::get_price_config(&env, &asset)
+    }
+
+    fn open_position(env: Env, user: Address, input: i128, size: u32, token: Address, side: Side, acceptable_price: i128) -> (u32, i128) {
+        storage::extend_instance(&env);
+
+        //User must authenticate the opening of a position
+        user.require_auth();
+        position::validate_token(&env, &token);
+
+        // Create the position
+        let id = storage::next_position_id(&env, &user);
+        let mut prices = PriceContext::new(&env, true);
+        let (mut position, fee) = position::new_market_position(&env, &mut prices, id, input, size, token, side);
+        position::validate_acceptable_price(&env, side, position.entry_price, acceptable_price);
+        let to_borrow = position.borrowed;
 
         // Transfer the collateral to the position manager
-        let token_client = TokenClient::new(&env, &token);
+        let collateral_token = position::collateral_token(&env, &position);
+        position::validate_position_limits(&env, &collateral_token, input, size as i128);
+        let token_client = TokenClient::new(&env, &collateral_token);
         token_client.transfer(&user, &env.current_contract_address(), &(input + fee));
 
         // Borrow the token from the pool
-        let pool_contract = storage::get_pool_contract(&env);
-        let pool_client = PoolClient::new(&env, &pool_contract);
-
-        let args: Vec<Val> = vec![
-            &env,
-            (env.current_contract_address()).into_val(&env),
-            pool_contract.into_val(&env),
-            fee.into_val(&env),
-        ];
-        env.authorize_as_current_contract(vec![
-            &env,
-            InvokerContractAuthEntry::Contract(SubContractInvocation {
-                context: ContractContext {
-                    contract: token.clone(),
-                    fn_name: Symbol::new(&env, "transfer"),
-                    args: args.clone(),
-                },
-                sub_invocations: vec![&env],
-            }),
-        ]);
-        pool_client.borrow(&token, &to_borrow, &fee);
+        position.borrow_index = position::borrow(&env, collateral_token, to_borrow, fee);
+        position::increase_open_interest(&env, &position.token, side, to_borrow, position.entry_price);
 
         storage::set_position(&env, &user, &position);
-        fee
+        events::open_position(&env, user, position, fee);
+        (id, fee)
     }
 
-    fn open_limit_position(env: Env, user: Address, input: i128, size: u32, token: Address, entry_price: i128) -> i128 {
+    fn open_limit_position(env: Env, user: Address, input: i128, size: u32, token: Address, side: Side, entry_price: i128) -> (u32, i128) {
         storage::extend_instance(&env);
 
         //User must authenticate the opening of a position
         user.require_auth();
+        position::validate_token(&env, &token);
 
-        // Only 1 position per user
-        if storage::has_position(&env, &user) {
-            panic_with_error!(&env, PositionManagerError::PositionAlreadyExists);
+        if entry_price <= 0 {
+            panic_with_error!(&env, PositionManagerError::InvalidPrice);
         }
 
-        let position = Position {
+        let id = storage::next_position_id(&env, &user);
+        let to_borrow = input.fixed_mul_floor(&env, &(size as i128), &SCALAR_7);
+        let fee = position::calculate_impact_fee(&env, to_borrow, position::calculate_notional(&env, &token, side, to_borrow, entry_price));
+        let mut position = Position {
+            id,
             filled: false,
             token: token.clone(),
+            side,
             stop_loss: 0,
             take_profit: 0,
             entry_price,
             borrowed: 0,
             leverage: size,
             collateral: input,
+            prepaid_fee: fee,
+            keeper_fee: 0,
+            borrow_index: 0,
             timestamp: env.ledger().timestamp(),
         };
 
-        let to_borrow = input.fixed_mul_floor(&env, &(size as i128), &SCALAR_7);
-        let fee = position::calculate_impact_fee(&env, to_borrow, entry_price);
-        let token_client = TokenClient::new(&env, &token);
+        let collateral_token = position::collateral_token(&env, &position);
+        position::validate_position_limits(&env, &collateral_token, input, size as i128);
+        let token_client = TokenClient::new(&env, &collateral_token);
         token_client.transfer(&user, &env.current_contract_address(), &(input + fee));
+        position::reserve_keeper_fee(&env, &user, &mut position);
 
         storage::set_position(&env, &user, &position);
-        fee
+        events::open_limit_position(&env, user, position, fee);
+        (id, fee)
     }
 
-    fn add_stop_loss(env: Env, user: Address, stop_loss: i128) {
+    fn cancel_limit_position(env: Env, user: Address, id: u32) -> i128 {
         storage::extend_instance(&env);
 
         user.require_auth();
 
-        if !storage::has_position(&env, &user) {
+        if !storage::has_position(&env, &user, id) {
             panic_with_error!(&env, PositionManagerError::NoPositionExists);
         }
 
-        let mut position = storage::get_position(&env, &user);
+        let position = storage::get_position(&env, &user, id);
+        if position.filled {
+            panic_with_error!(&env, PositionManagerError::PositionAlreadyFilled);
+        }
+
+        // Return the collateral, the prepaid impact fee and the reserved keeper fee
+        let refund = position.collateral + position.prepaid_fee + position.keeper_fee;
+        let token_client = TokenClient::new(&env, &position::collateral_token(&env, &position));
+        token_client.transfer(&env.current_contract_address(), &user, &refund);
+
+        storage::remove_position(&env, &user, id);
+
+        events::cancel_limit_position(&env, user, id, position.collateral, position.prepaid_fee);
+        refund
+    }
+
+    fn add_stop_loss(env: Env, user: Address, id: u32, stop_loss: i128) {
+        storage::extend_instance(&env);
+
+        user.require_auth();
+
+        if !storage::has_position(&env, &user, id) {
+            panic_with_error!(&env, PositionManagerError::NoPositionExists);
+        }
+
+        let mut position = storage::get_position(&env, &user, id);
         position.stop_loss = stop_loss;
+        position::reserve_keeper_fee(&env, &user, &mut position);
 
         storage::set_position(&env, &user, &position);
+        events::add_stop_loss(&env, user, position);
     }
 
-    fn add_take_profit(env: Env, user: Address, take_profit: i128) {
+    fn add_take_profit(env: Env, user: Address, id: u32, take_profit: i128) {
         storage::extend_instance(&env);
 
         user.require_auth();
 
-        if !storage::has_position(&env, &user) {
+        if !storage::has_position(&env, &user, id) {
             panic_with_error!(&env, PositionManagerError::NoPositionExists);
         }
 
-        let mut position = storage::get_position(&env, &user);
+        let mut position = storage::get_position(&env, &user, id);
         position.take_profit = take_profit;
+        position::reserve_keeper_fee(&env, &user, &mut position);
 
         storage::set_position(&env, &user, &position);
+        events::add_take_profit(&env, user, position);
     }
 
-    fn fill_position(env: Env, user: Address, fee_taker: Address) {
-        //TODO: Reward user calling part of the fee
+    fn fill_position(env: Env, user: Address, id: u32, fee_taker: Address) {
         storage::extend_instance(&env);
 
-        if !storage::has_position(&env, &user) {
+        if !storage::has_position(&env, &user, id) {
             panic_with_error!(&env, PositionManagerError::NoPositionExists);
         }
 
-        let position = storage::get_position(&env, &user);
-        let token = position.token.clone();
-        let oracle = storage::get_oracle(&env);
-        let current_price = oracle::load_relative_price(&env, oracle.clone(), token.clone());
+        let position = storage::get_position(&env, &user, id);
+        let token = position::collateral_token(&env, &position);
+        // Filling a limit order opens exposure at the trader's entry price and is guarded against
+        // price deviation, take profit and stop loss orders only close it at the exit price
+        let (price_mode, check_deviation) = if position.filled {
+            (position::exit_price_mode(position.side), false)
+        } else {
+            (position::entry_price_mode(position.side), true)
+        };
+        let mut prices = PriceContext::new(&env, check_deviation);
+        let current_price = prices.relative_price(&env, &position.token, price_mode);
+        let fill_share = storage::get_keeper_config(&env, &token).fill_share;
 
         if position.filled {
-            if (position.take_profit != 0 && current_price >= position.take_profit) || (position.stop_loss != 0 && current_price <= position.stop_loss) {
+            if position::is_take_profit_or_stop_loss_hit(&position, current_price) {
                 let total_position = position.borrowed + position.collateral;
-                let (to_repay, fee) = position::calculate_repay_and_fee(&env, position.clone());
+                let (to_repay, fee) = position::calculate_repay_and_fee(&env, &mut prices, position.clone(), price_mode);
                 let to_repay_user = total_position - to_repay - fee;
+                let keeper_share = position::calculate_keeper_share(&env, fee, fill_share);
+
+                position::close(&env, user.clone(), &position, to_repay_user, to_repay, fee - keeper_share);
+
+                // Reward the keeper with the reserved keeper fee and its share of the fee
+                let keeper_reward = position.keeper_fee + keeper_share;
+                if keeper_reward > 0 {
+                    let token_client = TokenClient::new(&env, &token);
+                    token_client.transfer(&env.current_contract_address(), &fee_taker, &keeper_reward);
+                }
 
-                position::repay(&env, token, user, to_repay_user, to_repay, fee);
+                let close = ClosePositionQuote { price: current_price, to_repay, fee, payout: to_repay_user };
+                events::trigger_position(&env, user, id, fee_taker, close, keeper_reward);
             }
         } else {
-            if position.entry_price < current_price {
+            // Longs fill at or below the limit price, shorts at or above it, at the trader's entry price
+            let fillable = match position.side {
+                Side::Long => current_price <= position.entry_price,
+                Side::Short => current_price >= position.entry_price,
+            };
+            if !fillable {
                 panic_with_error!(&env, PositionManagerError::PositionNotFilled);
             }
 
+            // Limits may have changed since the order was placed
+            position::validate_position_limits(&env, &token, position.collateral, position.leverage as i128);
+
+            // The impact fee was prepaid when the order was placed
             let to_borrow = position.collateral.fixed_mul_floor(&env, &(position.leverage as i128), &SCALAR_7);
-            let fee = position::calculate_impact_fee(&env, to_borrow, position.entry_price);
-            let new_position = Position {
+            let fee = position.prepaid_fee;
+            let keeper_share = position::calculate_keeper_share(&env, fee, fill_share);
+            let mut new_position = Position {
+                id,
                 filled: true,
                 token: position.token.clone(),
+                side: position.side,
                 stop_loss: position.stop_loss,
                 take_profit: position.take_profit,
                 entry_price: current_price,
                 borrowed: to_borrow,
                 leverage: position.leverage,
                 collateral: position.collateral,
+                prepaid_fee: 0,
+                // The reserve stays with the position for the keeper triggering its take profit or stop loss
+                keeper_fee: position.keeper_fee,
+                borrow_index: 0,
                 timestamp: env.ledger().timestamp(),
             };
 
-            let pool_contract = storage::get_pool_contract(&env);
-            let pool_client = PoolClient::new(&env, &pool_contract);
-
-            let args: Vec<Val> = vec![
-                &env,
-                (env.current_contract_address()).into_val(&env),
-                pool_contract.into_val(&env),
-                fee.into_val(&env),
-            ];
-            env.authorize_as_current_contract(vec![
-                &env,
-                InvokerContractAuthEntry::Contract(SubContractInvocation {
-                    context: ContractContext {
-                        contract: token.clone(),
-                        fn_name: Symbol::new(&env, "transfer"),
-                        args: args.clone(),
-                    },
-                    sub_invocations: vec![&env],
-                }),
-            ]);
-            pool_client.borrow(&token, &to_borrow, &fee);
+            new_position.borrow_index = position::borrow(&env, token.clone(), to_borrow, fee - keeper_share);
+            position::increase_open_interest(&env, &new_position.token, new_position.side, to_borrow, current_price);
+
+            // Reward the keeper with its share of the fee
+            if keeper_share > 0 {
+                let token_client = TokenClient::new(&env, &token);
+                token_client.transfer(&env.current_contract_address(), &fee_taker, &keeper_share);
+            }
 
             storage::set_position(&env, &user, &new_position);
+            events::fill_position(&env, user, new_position, fee_taker, keeper_share);
         }
     }
 
-    fn close_position(env: Env, user: Address) -> (i128, i128) {
+    fn close_position(env: Env, user: Address, id: u32, min_payout: i128) -> (i128, i128) {
         storage::extend_instance(&env);
 
         user.require_auth();
 
-        if !storage::has_position(&env, &user) {
+        if !storage::has_position(&env, &user, id) {
             panic_with_error!(&env, PositionManagerError::NoPositionExists);
         }
 
-        let position = storage::get_position(&env, &user);
+        let position = storage::get_position(&env, &user, id);
+        if !position.filled {
+            panic_with_error!(&env, PositionManagerError::PositionNotFilled);
+        }
+
+        let quote = position::calculate_close(&env, &mut PriceContext::new(&env, false), &position);
+        if quote.payout < min_payout {
+            panic_with_error!(&env, PositionManagerError::PayoutTooLow);
+        }
+
+        position::close(&env, user.clone(), &position, quote.payout, quote.to_repay, quote.fee);
+        events::close_position(&env, user, id, quote.clone());
+        (quote.payout, quote.fee)
+    }
+
+    fn decrease_position(env: Env, user: Address, id: u32, amount: DecreaseAmount) -> (i128, i128) {
+        storage::extend_instance(&env);
+
+        user.require_auth();
+
+        if !storage::has_position(&env, &user, id) {
+            panic_with_error!(&env, PositionManagerError::NoPositionExists);
+        }
+
+        let mut position = storage::get_position(&env, &user, id);
+        if !position.filled {
+            panic_with_error!(&env, PositionManagerError::PositionNotFilled);
+        }
+
+        let collateral = match amount {
+            DecreaseAmount::Fraction(fraction) => {
+                if fraction <= 0 || fraction >= SCALAR_7 {
+                    panic_with_error!(&env, PositionManagerError::InvalidInput);
+                }
+                position.collateral.fixed_mul_floor(&env, &fraction, &SCALAR_7)
+            }
+            DecreaseAmount::Collateral(collateral) => collateral,
+        };
+        if collateral <= 0 || collateral >= position.collateral {
+            panic_with_error!(&env, PositionManagerError::InvalidInput);
+        }
+
+        let quote = position::calculate_close(&env, &mut PriceContext::new(&env, false), &position);
 
-        let total_position = position.borrowed + position.collateral;
-        let (to_repay, fee) = position::calculate_repay_and_fee(&env, position.clone());
-        let to_repay_user = total_position - to_repay - fee;
+        // Realize the collateral's share of the position being closed
+        let borrowed = position.borrowed.fixed_mul_floor(&env, &collateral, &position.collateral);
+        let to_repay = quote.to_repay.fixed_mul_ceil(&env, &collateral, &position.collateral);
+        let fee = quote.fee.fixed_mul_ceil(&env, &collateral, &position.collateral);
+        let to_repay_user = borrowed + collateral - to_repay - fee;
+        if to_repay_user < 0 {
+            panic_with_error!(&env, PositionManagerError::InsufficientCollateral);
+        }
+
+        // The rest of the position must stay within the position limits
+        let collateral_token = position::collateral_token(&env, &position);
+        let remaining_borrowed = position.borrowed - borrowed;
+        let remaining_collateral = position.collateral - collateral;
+        let leverage = remaining_borrowed.fixed_div_floor(&env, &remaining_collateral, &SCALAR_7);
+        position::validate_position_limits(&env, &collateral_token, remaining_collateral, leverage);
+
+        position::repay(&env, collateral_token.clone(), to_repay, fee, borrowed);
+        position::decrease_open_interest(&env, &position.token, position.side, borrowed, position.entry_price);
+
+        let token_client = TokenClient::new(&env, &collateral_token);
+        token_client.transfer(&env.current_contract_address(), &user, &to_repay_user);
+
+        position.borrowed = remaining_borrowed;
+        position.collateral = remaining_collateral;
+        position.leverage = leverage as u32;
+        storage::set_position(&env, &user, &position);
 
-        position::repay(&env, position.token.clone(), user, to_repay_user, to_repay, fee);
+        let close = ClosePositionQuote { price: quote.price, to_repay, fee, payout: to_repay_user };
+        events::decrease_position(&env, user, position, collateral, close);
         (to_repay_user, fee)
     }
 
-    fn liquidate(env: Env, user: Address, liquidator: Address) {
+    fn increase_position(env: Env, user: Address, id: u32, collateral: i128, leverage: u32, acceptable_price: i128) -> i128 {
+        storage::extend_instance(&env);
+
+        user.require_auth();
+
+        if !storage::has_position(&env, &user, id) {
+            panic_with_error!(&env, PositionManagerError::NoPositionExists);
+        }
+
+        let mut position = storage::get_position(&env, &user, id);
+        if !position.filled {
+            panic_with_error!(&env, PositionManagerError::PositionNotFilled);
+        }
+
+        if collateral <= 0 {
+            panic_with_error!(&env, PositionManagerError::InvalidInput);
+        }
+        if (leverage as i128) < MIN_LEVERAGE {
+            panic_with_error!(&env, PositionManagerError::LeverageTooLow);
+        }
+
+        let mut prices = PriceContext::new(&env, true);
+        let current_price = prices.relative_price(&env, &position.token, position::entry_price_mode(position.side));
+        position::validate_acceptable_price(&env, position.side, current_price, acceptable_price);
+
+        // Settle the borrow fee accrued so far, the merged position accrues from now on
+        let accrued_fee = position::calculate_borrow_fee(&env, &position);
+
+        let to_borrow = collateral.fixed_mul_floor(&env, &(leverage as i128), &SCALAR_7);
+        let impact_fee = position::calculate_impact_fee(&env, to_borrow, position::calculate_notional(&env, &position.token, position.side, to_borrow, current_price));
+        let fee = accrued_fee + impact_fee;
+
+        let collateral_token = position::collateral_token(&env, &position);
+        let new_collateral = position.collateral + collateral;
+        let new_leverage = (position.borrowed + to_borrow).fixed_div_floor(&env, &new_collateral, &SCALAR_7);
+        position::validate_position_limits(&env, &collateral_token, new_collateral, new_leverage);
+
+        let token_client = TokenClient::new(&env, &collateral_token);
+        token_client.transfer(&user, &env.current_contract_address(), &(collateral + fee));
+
+        position.borrow_index = position::borrow(&env, collateral_token, to_borrow, fee);
+        position::increase_open_interest(&env, &position.token, position.side, to_borrow, current_price);
+
+        position.entry_price = position::calculate_average_entry_price(&env, &position, to_borrow, current_price);
+        position.borrowed += to_borrow;
+        position.collateral = new_collateral;
+        position.leverage = new_leverage as u32;
+        position.timestamp = env.ledger().timestamp();
+        storage::set_position(&env, &user, &position);
+
+        events::increase_position(&env, user, position, collateral, current_price, fee);
+        fee
+    }
+
+    fn add_collateral(env: Env, user: Address, id: u32, amount: i128) {
         storage::extend_instance(&env);
 
-        if !storage::has_position(&env, &user) {
+        user.require_auth();
+
+        if !storage::has_position(&env, &user, id) {
             panic_with_error!(&env, PositionManagerError::NoPositionExists);
         }
 
-        let position = storage::get_position(&env, &user);
+        if amount <= 0 {
+            panic_with_error!(&env, PositionManagerError::InvalidInput);
+        }
+
+        let mut position = storage::get_position(&env, &user, id);
+        if !position.filled {
+            panic_with_error!(&env, PositionManagerError::PositionNotFilled);
+        }
+
+        let token_client = TokenClient::new(&env, &position::collateral_token(&env, &position));
+        token_client.transfer(&user, &env.current_contract_address(), &amount);
 
-        let current_price = oracle::load_relative_price(&env, storage::get_oracle(&env), position.token.clone());
-        let borrowed_value = position.borrowed.fixed_mul_floor(&env, &current_price, &SCALAR_7);
-        let collateral_value = position.collateral.fixed_mul_floor(&env, &current_price, &SCALAR_7);
-        let (to_repay, fee) = position::calculate_repay_and_fee(&env, position.clone());
+        position.collateral += amount;
+        position.leverage = position.borrowed.fixed_div_floor(&env, &position.collateral, &SCALAR_7) as u32;
+        storage::set_position(&env, &user, &position);
+        events::add_collateral(&env, user, position, amount);
+    }
 
-        let size_ratio = borrowed_value.fixed_div_floor(&env, &MAX_LEVERAGE, &SCALAR_7);
-        let mut liquidation_premium = collateral_value - fee - size_ratio;
-        liquidation_premium = liquidation_premium.fixed_mul_floor(&env, &current_price, &SCALAR_7);
-        liquidation_premium = liquidation_premium.fixed_div_floor(&env, &position.borrowed, &SCALAR_7);
+    fn remove_collateral(env: Env, user: Address, id: u32, amount: i128) {
+        storage::extend_instance(&env);
 
-        let liquidation_price = position.entry_price + liquidation_premium;
+        user.require_auth();
 
-        if current_price <= liquidation_price {
-            let borrowed_value = position.borrowed.fixed_mul_floor(&env, &position.entry_price, &SCALAR_7);
-            let to_repay = borrowed_value.fixed_div_floor(&env, &current_price, &SCALAR_7);
-            let liquidation_fee = position.borrowed + position.collateral - to_repay;
+        if !storage::has_position(&env, &user, id) {
+            panic_with_error!(&env, PositionManagerError::NoPositionExists);
+        }
+
+        let mut position = storage::get_position(&env, &user, id);
+        if !position.filled {
+            panic_with_error!(&env, PositionManagerError::PositionNotFilled);
+        }
+
+        if amount <= 0 || amount >= position.collateral {
+            panic_with_error!(&env, PositionManagerError::InvalidInput);
+        }
+
+        let collateral_token = position::collateral_token(&env, &position);
+        position.collateral -= amount;
+        let leverage = position.borrowed.fixed_div_floor(&env, &position.collateral, &SCALAR_7);
+        position::validate_position_limits(&env, &collateral_token, position.collateral, leverage);
+        position.leverage = leverage as u32;
+
+        // The position must stay above the maintenance margin after the removal
+        let mut prices = PriceContext::new(&env, true);
+        let current_price = prices.relative_price(&env, &position.token, PriceMode::Spot);
+        let (_, fee) = position::calculate_repay_and_fee(&env, &mut prices, position.clone(), PriceMode::Spot);
+        if position::is_liquidatable(&env, &position, current_price, fee) {
+            panic_with_error!(&env, PositionManagerError::InsufficientCollateral);
+        }
+
+        let token_client = TokenClient::new(&env, &collateral_token);
+        token_client.transfer(&env.current_contract_address(), &user, &amount);
+
+        storage::set_position(&env, &user, &position);
+        events::remove_collateral(&env, user, position, amount);
+    }
+
+    fn liquidate(env: Env, user: Address, id: u32, liquidator: Address) {
+        storage::extend_instance(&env);
+
+        if !storage::has_position(&env, &user, id) {
+            panic_with_error!(&env, PositionManagerError::NoPositionExists);
+        }
+
+        let position = storage::get_position(&env, &user, id);
+        if !position.filled {
+            panic_with_error!(&env, PositionManagerError::PositionNotFilled);
+        }
+
+        let quote = position::calculate_liquidation(&env, &mut PriceContext::new(&env, false), &position);
+
+        if quote.liquidatable {
+            let to_repay = quote.to_repay;
+            let liquidation_fee = quote.liquidation_fee;
+            let collateral_token = position::collateral_token(&env, &position);
 
             // Liquidate the position
-            let pool_contract = storage::get_pool_contract(&env);
-            let pool_client = PoolClient::new(&env, &pool_contract);
-            let token_client = TokenClient::new(&env, &position.token);
-
-            let args: Vec<Val> = vec![
-                &env,
-                (env.current_contract_address()).into_val(&env),
-                pool_contract.into_val(&env),
-                (to_repay + liquidation_fee).into_val(&env),
-            ];
-            env.authorize_as_current_contract(vec![
-                &env,
-                InvokerContractAuthEntry::Contract(SubContractInvocation {
-                    context: ContractContext {
-                        contract: position.token.clone(),
-                        fn_name: Symbol::new(&env, "transfer"),
-                        args: args.clone(),
-                    },
-                    sub_invocations: vec![&env],
-                }),
-            ]);
-            pool_client.repay(&position.token, &to_repay, &liquidation_fee);
+            position::repay(&env, collateral_token.clone(), to_repay, liquidation_fee, position.borrowed);
+            position::decrease_open_interest(&env, &position.token, position.side, position.borrowed, position.entry_price);
+
+            // Reward the liquidator with the reserved keeper fee and its share of the liquidation fee
+            let keeper_reward = quote.keeper_reward;
+            if keeper_reward > 0 {
+                let token_client = TokenClient::new(&env, &collateral_token);
+                token_client.transfer(&env.current_contract_address(), &liquidator, &keeper_reward);
+            }
 
             // Remove the position
-            storage::remove_position(&env, &user);
+            storage::remove_position(&env, &user, id);
+            events::liquidate(&env, user, id, liquidator, quote);
         } else {
             panic_with_error!(env, PositionManagerError::PositionNotLiquidatable);
         }
     }
 
-    fn get_position(env: Env, user: Address) -> Position {
+    fn get_position(env: Env, user: Address, id: u32) -> Position {
+        storage::extend_instance(&env);
+
+        if !storage::has_position(&env, &user, id) {
+            panic_with_error!(&env, PositionManagerError::NoPositionExists);
+        }
+
+        storage::get_position(&env, &user, id)
+    }
+
+    fn get_positions(env: Env, user: Address) -> Vec<Position> {
+        storage::extend_instance(&env);
+
+        let mut positions = Vec::new(&env);
+        for id in storage::get_user_positions(&env, &user).iter() {
+            positions.push_back(storage::get_position(&env, &user, id));
+        }
+        positions
+    }
+
+    fn get_open_interest(env: Env, token: Address) -> OpenInterest {
+        storage::extend_instance(&env);
+        storage::get_open_interest(&env, &token)
+    }
+
+    fn get_quote_token(env: Env) -> Address {
+        storage::extend_instance(&env);
+        storage::get_quote_token(&env)
+    }
+
+    fn get_global_pnl(env: Env) -> i128 {
+        storage::extend_instance(&env);
+        position::calculate_global_pnl(&env, &mut PriceContext::new(&env, false))
+    }
+
+    fn quote_open_position(env: Env, collateral: i128, size: u32, token: Address, side: Side) -> OpenPositionQuote {
+        storage::extend_instance(&env);
+        position::validate_token(&env, &token);
+
+        let (mut position, fee) = position::new_market_position(&env, &mut PriceContext::new(&env, true), 0, collateral, size, token, side);
+        let collateral_token = position::collateral_token(&env, &position);
+        position::validate_position_limits(&env, &collateral_token, collateral, size as i128);
+        position.borrow_index = position::calculate_borrow_index(&env, &collateral_token);
+
+        let close_fee = position::calculate_close_fee(&env, &position, position.entry_price);
+        OpenPositionQuote {
+            entry_price: position.entry_price,
+            borrowed: position.borrowed,
+            fee,
+            liquidation_price: position::calculate_liquidation_price(&env, &position, close_fee),
+        }
+    }
+
+    fn quote_close_position(env: Env, user: Address, id: u32) -> ClosePositionQuote {
         storage::extend_instance(&env);
 
-        if !storage::has_position(&env, &user) {
+        if !storage::has_position(&env, &user, id) {
             panic_with_error!(&env, PositionManagerError::NoPositionExists);
         }
 
-        storage::get_position(&env, &user)
+        position::calculate_close(&env, &mut PriceContext::new(&env, false), &storage::get_position(&env, &user, id))
     }
-}
\ No newline at end of file
+
+    fn quote_liquidation(env: Env, user: Address, id: u32) -> LiquidationQuote {
+        storage::extend_instance(&env);
+
+        if !storage::has_position(&env, &user, id) {
+            panic_with_error!(&env, PositionManagerError::NoPositionExists);
+        }
+
+        let position = storage::get_position(&env, &user, id);
+        if !position.filled {
+            panic_with_error!(&env, PositionManagerError::PositionNotFilled);
+        }
+
+        position::calculate_liquidation(&env, &mut PriceContext::new(&env, false), &position)
+    }
+}
@@ -13,10 +13,14 @@ pub enum PositionManagerError {
 
     // Oracle-related errors
     StalePriceData = 604,
+    PriceDeviation = 619,
 
     // Liquidation-related errors
     PositionNotLiquidatable = 605,
 
+    // Order errors
+    InvalidPrice = 606,
+
     // Calculation errors
     OverflowError = 607,
 
@@ -30,6 +34,18 @@ pub enum PositionManagerError {
 
     PositionNotFilled = 611,
 
+    InsufficientCollateral = 612,
+
+    // Position limit errors
+    LeverageTooLow = 613,
+    LeverageTooHigh = 614,
+    CollateralTooLow = 615,
+    NotionalTooLow = 616,
+
+    // Slippage errors
+    PriceNotAcceptable = 617,
+    PayoutTooLow = 618,
+
     // General errors
     InvalidInput = 10,
 }
\ No newline at end of file
@@ -0,0 +1,167 @@
+use soroban_sdk::{symbol_short, Address, Env, Symbol};
+use crate::storage::{ClosePositionQuote, FeeConfig, KeeperConfig, LiquidationQuote, Position, PositionLimits, PriceConfig};
+
+/// Tag and version of the event schema, the first topic of every event
+const EVENT_TAG: Symbol = symbol_short!("pm_v1");
+
+/// Emitted when the contract is initialized
+///
+/// - topics - `["pm_v1", "initialize", admin: Address]`
+/// - data - `[pool_contract: Address, oracle: Address, quote_token: Address]`
+pub fn initialize(env: &Env, admin: Address, pool_contract: Address, oracle: Address, quote_token: Address) {
+    let topics = (EVENT_TAG, Symbol::new(env, "initialize"), admin);
+    env.events().publish(topics, (pool_contract, oracle, quote_token));
+}
+
+/// Emitted when the admin sets the position limits of a collateral token
+///
+/// - topics - `["pm_v1", "set_position_limits", admin: Address]`
+/// - data - `[token: Address, limits: PositionLimits]`
+pub fn set_position_limits(env: &Env, admin: Address, token: Address, limits: PositionLimits) {
+    let topics = (EVENT_TAG, Symbol::new(env, "set_position_limits"), admin);
+    env.events().publish(topics, (token, limits));
+}
+
+/// Emitted when the admin sets the keeper configuration of a collateral token
+///
+/// - topics - `["pm_v1", "set_keeper_config", admin: Address]`
+/// - data - `[token: Address, config: KeeperConfig]`
+pub fn set_keeper_config(env: &Env, admin: Address, token: Address, config: KeeperConfig) {
+    let topics = (EVENT_TAG, Symbol::new(env, "set_keeper_config"), admin);
+    env.events().publish(topics, (token, config));
+}
+
+/// Emitted when the admin updates the fee configuration
+///
+/// - topics - `["pm_v1", "set_fee_config", admin: Address]`
+/// - data - `[base_fee: i128, hourly_base_fee: i128, impact_fee_scalar: i128]`
+pub fn set_fee_config(env: &Env, admin: Address, config: FeeConfig) {
+    let topics = (EVENT_TAG, Symbol::new(env, "set_fee_config"), admin);
+    env.events().publish(topics, (config.base_fee, config.hourly_base_fee, config.impact_fee_scalar));
+}
+
+/// Emitted when the admin sets the oracle limits of an asset
+///
+/// - topics - `["pm_v1", "set_price_config", admin: Address]`
+/// - data - `[asset: Address, max_age: u64, max_deviation: i128, twap_records: u32]`
+pub fn set_price_config(env: &Env, admin: Address, asset: Address, config: PriceConfig) {
+    let topics = (EVENT_TAG, Symbol::new(env, "set_price_config"), admin);
+    env.events().publish(topics, (asset, config.max_age, config.max_deviation, config.twap_records));
+}
+
+/// Emitted when a market position is opened
+///
+/// - topics - `["pm_v1", "open_position", user: Address]`
+/// - data - `[position: Position, fee: i128]`
+pub fn open_position(env: &Env, user: Address, position: Position, fee: i128) {
+    let topics = (EVENT_TAG, Symbol::new(env, "open_position"), user);
+    env.events().publish(topics, (position, fee));
+}
+
+/// Emitted when a limit order is placed
+///
+/// - topics - `["pm_v1", "open_limit_position", user: Address]`
+/// - data - `[position: Position, fee: i128]`
+pub fn open_limit_position(env: &Env, user: Address, position: Position, fee: i128) {
+    let topics = (EVENT_TAG, Symbol::new(env, "open_limit_position"), user);
+    env.events().publish(topics, (position, fee));
+}
+
+/// Emitted when a pending limit order is cancelled
+///
+/// - topics - `["pm_v1", "cancel_limit_position", user: Address]`
+/// - data - `[id: u32, collateral: i128, fee: i128]`
+pub fn cancel_limit_position(env: &Env, user: Address, id: u32, collateral: i128, fee: i128) {
+    let topics = (EVENT_TAG, Symbol::new(env, "cancel_limit_position"), user);
+    env.events().publish(topics, (id, collateral, fee));
+}
+
+/// Emitted when a keeper fills a limit order
+///
+/// - topics - `["pm_v1", "fill_position", user: Address]`
+/// - data - `[position: Position, keeper: Address, keeper_reward: i128]`
+pub fn fill_position(env: &Env, user: Address, position: Position, keeper: Address, keeper_reward: i128) {
+    let topics = (EVENT_TAG, Symbol::new(env, "fill_position"), user);
+    env.events().publish(topics, (position, keeper, keeper_reward));
+}
+
+/// Emitted when a keeper closes a position at its take profit or stop loss
+///
+/// - topics - `["pm_v1", "trigger_position", user: Address]`
+/// - data - `[id: u32, keeper: Address, close: ClosePositionQuote, keeper_reward: i128]`
+pub fn trigger_position(env: &Env, user: Address, id: u32, keeper: Address, close: ClosePositionQuote, keeper_reward: i128) {
+    let topics = (EVENT_TAG, Symbol::new(env, "trigger_position"), user);
+    env.events().publish(topics, (id, keeper, close, keeper_reward));
+}
+
+/// Emitted when a user sets the stop loss of a position
+///
+/// - topics - `["pm_v1", "add_stop_loss", user: Address]`
+/// - data - `[id: u32, stop_loss: i128, keeper_fee: i128]`
+pub fn add_stop_loss(env: &Env, user: Address, position: Position) {
+    let topics = (EVENT_TAG, Symbol::new(env, "add_stop_loss"), user);
+    env.events().publish(topics, (position.id, position.stop_loss, position.keeper_fee));
+}
+
+/// Emitted when a user sets the take profit of a position
+///
+/// - topics - `["pm_v1", "add_take_profit", user: Address]`
+/// - data - `[id: u32, take_profit: i128, keeper_fee: i128]`
+pub fn add_take_profit(env: &Env, user: Address, position: Position) {
+    let topics = (EVENT_TAG, Symbol::new(env, "add_take_profit"), user);
+    env.events().publish(topics, (position.id, position.take_profit, position.keeper_fee));
+}
+
+/// Emitted when a user closes a position
+///
+/// - topics - `["pm_v1", "close_position", user: Address]`
+/// - data - `[id: u32, close: ClosePositionQuote]`
+pub fn close_position(env: &Env, user: Address, id: u32, close: ClosePositionQuote) {
+    let topics = (EVENT_TAG, Symbol::new(env, "close_position"), user);
+    env.events().publish(topics, (id, close));
+}
+
+/// Emitted when a user closes part of a position
+///
+/// - topics - `["pm_v1", "decrease_position", user: Address]`
+/// - data - `[position: Position, collateral: i128, close: ClosePositionQuote]`
+pub fn decrease_position(env: &Env, user: Address, position: Position, collateral: i128, close: ClosePositionQuote) {
+    let topics = (EVENT_TAG, Symbol::new(env, "decrease_position"), user);
+    env.events().publish(topics, (position, collateral, close));
+}
+
+/// Emitted when a user adds to a position
+///
+/// - topics - `["pm_v1", "increase_position", user: Address]`
+/// - data - `[position: Position, collateral: i128, price: i128, fee: i128]`
+pub fn increase_position(env: &Env, user: Address, position: Position, collateral: i128, price: i128, fee: i128) {
+    let topics = (EVENT_TAG, Symbol::new(env, "increase_position"), user);
+    env.events().publish(topics, (position, collateral, price, fee));
+}
+
+/// Emitted when a user adds collateral to a position
+///
+/// - topics - `["pm_v1", "add_collateral", user: Address]`
+/// - data - `[position: Position, amount: i128]`
+pub fn add_collateral(env: &Env, user: Address, position: Position, amount: i128) {
+    let topics = (EVENT_TAG, Symbol::new(env, "add_collateral"), user);
+    env.events().publish(topics, (position, amount));
+}
+
+/// Emitted when a user removes collateral from a position
+///
+/// - topics - `["pm_v1", "remove_collateral", user: Address]`
+/// - data - `[position: Position, amount: i128]`
+pub fn remove_collateral(env: &Env, user: Address, position: Position, amount: i128) {
+    let topics = (EVENT_TAG, Symbol::new(env, "remove_collateral"), user);
+    env.events().publish(topics, (position, amount));
+}
+
+/// Emitted when a position is liquidated
+///
+/// - topics - `["pm_v1", "liquidate", user: Address]`
+/// - data - `[id: u32, liquidator: Address, liquidation: LiquidationQuote]`
+pub fn liquidate(env: &Env, user: Address, id: u32, liquidator: Address, liquidation: LiquidationQuote) {
+    let topics = (EVENT_TAG, Symbol::new(env, "liquidate"), user);
+    env.events().publish(topics, (id, liquidator, liquidation));
+}
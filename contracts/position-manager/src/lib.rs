@@ -6,6 +6,7 @@ mod storage;
 mod errors;
 mod dependencies;
 mod constants;
+mod events;
 mod oracle;
 mod position;
 
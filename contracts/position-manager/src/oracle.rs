@@ -1,35 +1,138 @@
+use oracle_utils::{calculate_deviation, calculate_twap, normalize_price};
 use sep_40_oracle::{Asset, PriceFeedClient};
 use soroban_fixed_point_math::SorobanFixedPoint;
-use soroban_sdk::{Address, Env, panic_with_error};
+use soroban_sdk::{Address, Env, Map, panic_with_error};
 use soroban_sdk::unwrap::UnwrapOptimized;
 use crate::errors::PositionManagerError;
 use crate::{storage};
 use crate::constants::SCALAR_7;
 
-/// Load a price from the Pool's oracle without caching.
+/// How a price is derived from the oracle's records
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum PriceMode {
+    /// The latest price
+    Spot,
+    /// The time-weighted average of the asset's latest `twap_records` prices
+    Twap,
+    /// The lower of the spot price and the TWAP
+    Min,
+    /// The higher of the spot price and the TWAP
+    Max,
+}
+
+impl PriceMode {
+    /// The mode that bounds a price the other way, Min for Max and Max for Min
+    pub(crate) fn opposite(self) -> PriceMode {
+        match self {
+            PriceMode::Min => PriceMode::Max,
+            PriceMode::Max => PriceMode::Min,
+            mode => mode,
+        }
+    }
+}
+
+/// Oracle prices loaded during one contract invocation. Each asset is read from the oracle once,
+/// however often and in whichever modes its price is used afterwards.
+pub(crate) struct PriceContext {
+    oracle: Address,
+    quote_token: Address,
+    check_deviation: bool,
+    prices: Map<Address, (i128, i128)>,
+}
+
+impl PriceContext {
+    /// Create an empty context for an invocation. Invocations that open or add exposure set
+    /// `check_deviation`, see `load_price`.
+    pub(crate) fn new(e: &Env, check_deviation: bool) -> Self {
+        PriceContext {
+            oracle: storage::get_oracle(e),
+            quote_token: storage::get_quote_token(e),
+            check_deviation,
+            prices: Map::new(e),
+        }
+    }
+
+    /// Fetch the price of an asset, loading it from the oracle on first use
+    ///
+    /// ### Arguments
+    /// * e - The environment
+    /// * asset - The address of the underlying asset
+    /// * mode - How the price is derived from the oracle's records
+    ///
+    /// ### Returns
+    /// The price normalized to 7 decimals
+    ///
+    /// ### Panics
+    /// If the price is older than the asset's maximum age, or if the context checks deviation and the
+    /// price moved more than its maximum deviation from the previous oracle record
+    pub(crate) fn price(&mut self, e: &Env, asset: &Address, mode: PriceMode) -> i128 {
+        let (spot_price, twap) = match self.prices.get(asset.clone()) {
+            Some(prices) => prices,
+            None => {
+                let prices = load_price(e, &self.oracle, asset, self.check_deviation);
+                self.prices.set(asset.clone(), prices);
+                prices
+            }
+        };
+        match mode {
+            PriceMode::Spot => spot_price,
+            PriceMode::Twap => twap,
+            PriceMode::Min => spot_price.min(twap),
+            PriceMode::Max => spot_price.max(twap),
+        }
+    }
+
+    /// Fetch the price of a token in units of the quote token. The quote token is priced with the
+    /// opposite mode, so a Min or Max relative price is bounded the same way.
+    pub(crate) fn relative_price(&mut self, e: &Env, token: &Address, mode: PriceMode) -> i128 {
+        let quote_token = self.quote_token.clone();
+        let token_price = self.price(e, token, mode);
+        let quote_token_price = self.price(e, &quote_token, mode.opposite());
+        token_price.fixed_div_floor(e, &quote_token_price, &SCALAR_7)
+    }
+}
+
+/// Load the spot price and the TWAP of an asset from the oracle, use a PriceContext to avoid
+/// loading them more than once.
 ///
 /// ### Arguments
 /// * e - The environment
 /// * oracle - The address of the oracle contract
 /// * asset - The address of the underlying asset
+/// * check_deviation - Whether to guard against a price move from the previous record. Paths that
+///   only reduce exposure skip the guard so they keep working through genuine market moves.
+///
+/// ### Returns
+/// The spot price and the TWAP, normalized to 7 decimals
 ///
 /// ### Panics
-/// If the price is stale
-pub(crate) fn load_price(e: &Env, oracle: Address, asset: Address) -> i128 {
-    let oracle_client = PriceFeedClient::new(e, &oracle);
+/// If the price is older than the asset's maximum age, or if `check_deviation` is set and the price
+/// moved more than its maximum deviation from the previous oracle record
+pub(crate) fn load_price(e: &Env, oracle: &Address, asset: &Address, check_deviation: bool) -> (i128, i128) {
+    let config = storage::get_price_config(e, asset);
+    let oracle_client = PriceFeedClient::new(e, oracle);
     let oracle_asset = Asset::Stellar(asset.clone());
     let price_data = oracle_client.lastprice(&oracle_asset).unwrap_optimized();
-    if price_data.timestamp + 24 * 60 * 60 < e.ledger().timestamp() {
+    if price_data.timestamp + config.max_age < e.ledger().timestamp() {
         panic_with_error!(e, PositionManagerError::StalePriceData);
     }
-    price_data.price
+
+    // The latest record is the first in the history, compare it to the one before
+    let history = oracle_client.prices(&oracle_asset, &config.twap_records.max(2));
+    if check_deviation {
+        if let Some(previous) = history.as_ref().and_then(|history| history.get(1)) {
+            if previous.price > 0 && calculate_deviation(e, price_data.price, previous.price) > config.max_deviation {
+                panic_with_error!(e, PositionManagerError::PriceDeviation);
+            }
+        }
+    }
+
+    let decimals = storage::get_oracle_decimals(e);
+    let twap = calculate_twap(&history, config.twap_records, price_data.price);
+    (normalize_price(price_data.price, decimals), normalize_price(twap, decimals))
 }
 
-pub(crate) fn load_relative_price(env: &Env, oracle: Address, token: Address) -> i128 {
-    let token_a = storage::get_token_a(env);
-    let token_b = storage::get_token_b(env);
-    let other_token = if token == token_a { token_b } else { token_a };
-    let token_price = load_price(&env, oracle.clone(), token.clone());
-    let other_token_price = load_price(&env, oracle.clone(), other_token.clone());
-    return token_price.fixed_div_floor(env, &other_token_price, &SCALAR_7);
-}
\ No newline at end of file
+/// Load the number of decimals of an oracle's prices
+pub(crate) fn load_decimals(e: &Env, oracle: &Address) -> u32 {
+    PriceFeedClient::new(e, oracle).decimals()
+}
@@ -1,15 +1,254 @@
 use soroban_fixed_point_math::SorobanFixedPoint;
-use soroban_sdk::{Address, Env, IntoVal, Symbol, Val, Vec, vec};
+use soroban_sdk::{Address, Env, IntoVal, Symbol, Val, Vec, vec, panic_with_error};
 use soroban_sdk::auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation};
 use soroban_sdk::token::TokenClient;
-use crate::constants::{BASE_FEE, HOURLY_BASE_FEE, IMPACT_FEE_SCALAR, SCALAR_7};
+use crate::constants::{MAX_LEVERAGE, SCALAR_12, SCALAR_7};
+use crate::errors::PositionManagerError;
+use crate::oracle::{PriceContext, PriceMode};
 use crate::storage;
-use crate::storage::Position;
+use crate::storage::{BorrowIndex, ClosePositionQuote, LiquidationQuote, OpenInterest, Position, Side};
 
-pub(crate) fn repay(env: &Env, token: Address, user: Address, to_repay_user: i128, to_repay: i128, fee: i128) {
+/// Fetch the token a position is collateralized in and borrows from the pool.
+/// Longs use the traded token itself, shorts use the quote token.
+pub(crate) fn collateral_token(env: &Env, position: &Position) -> Address {
+    match position.side {
+        Side::Long => position.token.clone(),
+        Side::Short => storage::get_quote_token(env),
+    }
+}
+
+/// The price mode a trader opens or adds to a position at, the worse of the spot price and the TWAP for them
+pub(crate) fn entry_price_mode(side: Side) -> PriceMode {
+    match side {
+        Side::Long => PriceMode::Max,
+        Side::Short => PriceMode::Min,
+    }
+}
+
+/// The price mode a trader closes a position at, the worse of the spot price and the TWAP for them
+pub(crate) fn exit_price_mode(side: Side) -> PriceMode {
+    match side {
+        Side::Long => PriceMode::Min,
+        Side::Short => PriceMode::Max,
+    }
+}
+
+/// Validate that a token can be traded against the quote token
+///
+/// ### Panics
+/// If the token is the quote token
+pub(crate) fn validate_token(env: &Env, token: &Address) {
+    if *token == storage::get_quote_token(env) {
+        panic_with_error!(env, PositionManagerError::InvalidInput);
+    }
+}
+
+/// Validate a position's size against the limits configured for its collateral token
+///
+/// ### Arguments
+/// * `token` - The Address of the collateral token
+/// * `collateral` - The collateral of the position
+/// * `leverage` - The leverage of the position, scaled by SCALAR_7
+///
+/// ### Panics
+/// If the leverage, collateral or notional size are outside of the configured limits
+pub(crate) fn validate_position_limits(env: &Env, token: &Address, collateral: i128, leverage: i128) {
+    let limits = storage::get_position_limits(env, token);
+    if leverage < limits.min_leverage as i128 {
+        panic_with_error!(env, PositionManagerError::LeverageTooLow);
+    }
+    if leverage > limits.max_leverage as i128 {
+        panic_with_error!(env, PositionManagerError::LeverageTooHigh);
+    }
+    if collateral < limits.min_collateral {
+        panic_with_error!(env, PositionManagerError::CollateralTooLow);
+    }
+    let notional = collateral.fixed_mul_floor(env, &leverage, &SCALAR_7);
+    if notional < limits.min_notional {
+        panic_with_error!(env, PositionManagerError::NotionalTooLow);
+    }
+}
+
+/// Validate that an entry price is no worse than the price the user accepts. Longs must not
+/// enter above the acceptable price and shorts must not enter below it.
+///
+/// ### Panics
+/// If the entry price is worse than the acceptable price
+pub(crate) fn validate_acceptable_price(env: &Env, side: Side, entry_price: i128, acceptable_price: i128) {
+    let acceptable = match side {
+        Side::Long => entry_price <= acceptable_price,
+        Side::Short => entry_price >= acceptable_price,
+    };
+    if !acceptable {
+        panic_with_error!(env, PositionManagerError::PriceNotAcceptable);
+    }
+}
+
+/// Fetch the number of decimals of a token, caching it on first use
+pub(crate) fn load_token_decimals(env: &Env, token: &Address) -> u32 {
+    match storage::get_token_decimals(env, token) {
+        Some(decimals) => decimals,
+        None => {
+            let decimals = TokenClient::new(env, token).decimals();
+            storage::set_token_decimals(env, token, decimals);
+            decimals
+        }
+    }
+}
+
+/// Convert an amount from `from_token`'s decimals to `to_token`'s decimals
+pub(crate) fn convert_decimals(env: &Env, amount: i128, from_token: &Address, to_token: &Address) -> i128 {
+    let from_decimals = load_token_decimals(env, from_token);
+    let to_decimals = load_token_decimals(env, to_token);
+    if to_decimals >= from_decimals {
+        amount * 10i128.pow(to_decimals - from_decimals)
+    } else {
+        amount / 10i128.pow(from_decimals - to_decimals)
+    }
+}
+
+/// Calculate the notional size of a borrow in the quote token at the given price.
+/// Shorts already borrow the quote token so their notional needs no conversion.
+pub(crate) fn calculate_notional(env: &Env, token: &Address, side: Side, borrowed: i128, price: i128) -> i128 {
+    match side {
+        Side::Long => {
+            let notional = borrowed.fixed_mul_ceil(env, &price, &SCALAR_7);
+            convert_decimals(env, notional, token, &storage::get_quote_token(env))
+        }
+        Side::Short => borrowed,
+    }
+}
+
+/// Calculate the amount of the collateral token owed back to the pool at the given price.
+/// A long owes the value of its borrow at entry, a short owes the value of its borrow in the traded token.
+pub(crate) fn calculate_to_repay(env: &Env, position: &Position, current_price: i128) -> i128 {
+    match position.side {
+        Side::Long => {
+            let borrowed_value = position.borrowed.fixed_mul_floor(env, &position.entry_price, &SCALAR_7);
+            borrowed_value.fixed_div_floor(env, &current_price, &SCALAR_7)
+        }
+        Side::Short => {
+            let borrowed_value = position.borrowed.fixed_mul_ceil(env, &current_price, &SCALAR_7);
+            borrowed_value.fixed_div_ceil(env, &position.entry_price, &SCALAR_7)
+        }
+    }
+}
+
+/// Calculate the price at which the position's remaining equity drops to the maintenance margin
+///
+/// ### Arguments
+/// * `position` - The position to calculate the liquidation price for
+/// * `fee` - The fee owed when the position is closed
+pub(crate) fn calculate_liquidation_price(env: &Env, position: &Position, fee: i128) -> i128 {
+    let maintenance_margin = position.borrowed.fixed_div_floor(env, &MAX_LEVERAGE, &SCALAR_7);
+    let buffer = position.collateral - fee - maintenance_margin;
+    let remaining = position.borrowed + buffer;
+
+    match position.side {
+        Side::Long => {
+            if remaining <= 0 {
+                return i128::MAX;
+            }
+            position.entry_price.fixed_mul_ceil(env, &position.borrowed, &remaining)
+        }
+        Side::Short => {
+            if remaining <= 0 {
+                return 0;
+            }
+            position.entry_price.fixed_mul_floor(env, &remaining, &position.borrowed)
+        }
+    }
+}
+
+/// Check if the position has reached its liquidation price.
+/// Longs are liquidated when the price falls to the liquidation price, shorts when it rises to it.
+pub(crate) fn is_liquidatable(env: &Env, position: &Position, current_price: i128, fee: i128) -> bool {
+    let liquidation_price = calculate_liquidation_price(env, position, fee);
+    match position.side {
+        Side::Long => current_price <= liquidation_price,
+        Side::Short => current_price >= liquidation_price,
+    }
+}
+
+/// Calculate the entry price of a position after adding `added_borrow` at `current_price`.
+/// The result is the average of both entries weighted by their size in the traded token, so the
+/// position's debt is the same as if both parts had been kept separately.
+pub(crate) fn calculate_average_entry_price(env: &Env, position: &Position, added_borrow: i128, current_price: i128) -> i128 {
+    let total_borrowed = position.borrowed + added_borrow;
+    match position.side {
+        Side::Long => {
+            let value = position.borrowed.fixed_mul_floor(env, &position.entry_price, &SCALAR_7)
+                + added_borrow.fixed_mul_floor(env, &current_price, &SCALAR_7);
+            value.fixed_div_floor(env, &total_borrowed, &SCALAR_7)
+        }
+        Side::Short => {
+            let size = position.borrowed.fixed_div_floor(env, &position.entry_price, &SCALAR_7)
+                + added_borrow.fixed_div_floor(env, &current_price, &SCALAR_7);
+            total_borrowed.fixed_div_floor(env, &size, &SCALAR_7)
+        }
+    }
+}
+
+/// Check if the current price triggers the position's take profit or stop loss
+pub(crate) fn is_take_profit_or_stop_loss_hit(position: &Position, current_price: i128) -> bool {
+    match position.side {
+        Side::Long => (position.take_profit != 0 && current_price >= position.take_profit) || (position.stop_loss != 0 && current_price <= position.stop_loss),
+        Side::Short => (position.take_profit != 0 && current_price <= position.take_profit) || (position.stop_loss != 0 && current_price >= position.stop_loss),
+    }
+}
+
+/// Top up the keeper fee reserved on a position to the configured minimum,
+/// so the keeper executing the order is always paid
+pub(crate) fn reserve_keeper_fee(env: &Env, user: &Address, position: &mut Position) {
+    let token = collateral_token(env, position);
+    let min_keeper_fee = storage::get_keeper_config(env, &token).min_keeper_fee;
+    if position.keeper_fee < min_keeper_fee {
+        let token_client = TokenClient::new(env, &token);
+        token_client.transfer(user, &env.current_contract_address(), &(min_keeper_fee - position.keeper_fee));
+        position.keeper_fee = min_keeper_fee;
+    }
+}
+
+/// Calculate the share of a fee paid to the keeper executing an order
+pub(crate) fn calculate_keeper_share(env: &Env, fee: i128, share: i128) -> i128 {
+    if fee <= 0 {
+        return 0;
+    }
+    fee.fixed_mul_floor(env, &share, &SCALAR_7)
+}
+
+/// Borrow from the pool, returning the borrow index the new debt accrues from
+pub(crate) fn borrow(env: &Env, token: Address, amount: i128, fee: i128) -> i128 {
+    let pool_contract = storage::get_pool_contract(env);
+    let pool_client = crate::dependencies::pool::Client::new(env, &pool_contract);
+
+    let args: Vec<Val> = vec![
+        env,
+        (env.current_contract_address()).into_val(env),
+        pool_contract.into_val(env),
+        fee.into_val(env),
+    ];
+    env.authorize_as_current_contract(vec![
+        env,
+        InvokerContractAuthEntry::Contract(SubContractInvocation {
+            context: ContractContext {
+                contract: token.clone(),
+                fn_name: Symbol::new(env, "transfer"),
+                args: args.clone(),
+            },
+            sub_invocations: vec![env],
+        }),
+    ]);
+    pool_client.borrow(&token, &amount, &fee);
+
+    update_borrow_index(env, &token)
+}
+
+/// Repay part of a position's debt to the pool, sending it `to_repay` and the fee and releasing
+/// the `borrowed` amount reserved for it
+pub(crate) fn repay(env: &Env, token: Address, to_repay: i128, fee: i128, borrowed: i128) {
     let pool_contract = storage::get_pool_contract(env);
     let pool_client = crate::dependencies::pool::Client::new(env, &pool_contract);
-    let token_client = TokenClient::new(env, &token);
 
     let args: Vec<Val> = vec![
         env,
@@ -28,44 +267,234 @@ pub(crate) fn repay(env: &Env, token: Address, user: Address, to_repay_user: i12
             sub_invocations: vec![env],
         }),
     ]);
-    pool_client.repay(&token, &to_repay, &0);
+    pool_client.repay(&token, &to_repay, &fee, &borrowed);
+
+    update_borrow_index(env, &token);
+}
+
+/// Repay the whole of a position to the pool, pay the rest out to the user and remove the position
+pub(crate) fn close(env: &Env, user: Address, position: &Position, to_repay_user: i128, to_repay: i128, fee: i128) {
+    let token = collateral_token(env, position);
+    repay(env, token.clone(), to_repay, fee, position.borrowed);
+    decrease_open_interest(env, &position.token, position.side, position.borrowed, position.entry_price);
 
     // Transfer rest of position back
+    let token_client = TokenClient::new(env, &token);
     token_client.transfer(&env.current_contract_address(), &user, &to_repay_user);
 
-    storage::remove_position(env, &user);
+    storage::remove_position(env, &user, position.id);
 }
 
-pub(crate) fn calculate_impact_fee(env: &Env, borrow_size: i128, current_price: i128) -> i128 {
-    let trade_notional_size = borrow_size.fixed_mul_ceil(&env, &current_price, &SCALAR_7);
-    let trading_coefficient = trade_notional_size.fixed_div_ceil(&env, &IMPACT_FEE_SCALAR, &SCALAR_7);
-    BASE_FEE + trading_coefficient
+/// Calculate the impact fee rate for a trade with the given notional size in the quote token, scaled by SCALAR_7
+pub(crate) fn calculate_impact_fee_rate(env: &Env, trade_notional_size: i128) -> i128 {
+    let fee_config = storage::get_fee_config(env);
+    let trading_coefficient = trade_notional_size.fixed_div_ceil(&env, &fee_config.impact_fee_scalar, &SCALAR_7);
+    fee_config.base_fee + trading_coefficient
 }
 
-pub(crate) fn calculate_repay_and_fee(env: &Env, position: Position) -> (i128, i128) {
-    let oracle = storage::get_oracle(&env);
-    let current_price = crate::oracle::load_relative_price(&env, oracle, position.token.clone());
-    let borrowed_value = position.borrowed.fixed_mul_floor(&env, &position.entry_price, &SCALAR_7);
-    let mut to_repay = borrowed_value.fixed_div_floor(&env, &current_price, &SCALAR_7);
+/// Calculate the impact fee for borrowing `borrow_size` with the given notional size in the quote token,
+/// in the collateral token
+pub(crate) fn calculate_impact_fee(env: &Env, borrow_size: i128, trade_notional_size: i128) -> i128 {
+    calculate_impact_fee_rate(env, trade_notional_size).fixed_mul_ceil(env, &borrow_size, &SCALAR_7)
+}
 
-    // Hourly fee
-    let pool_contract = storage::get_pool_contract(&env);
-    let pool_client = crate::dependencies::pool::Client::new(&env, &pool_contract);
-    let token_client = TokenClient::new(&env, &position.token.clone());
+/// Calculate the amount repaid to the pool and the fee owed when closing a position at the price
+/// given by `mode`
+pub(crate) fn calculate_repay_and_fee(env: &Env, prices: &mut PriceContext, position: Position, mode: PriceMode) -> (i128, i128) {
+    let current_price = prices.relative_price(env, &position.token, mode);
+    let to_repay = calculate_to_repay(&env, &position, current_price);
+    let fee = calculate_close_fee(env, &position, current_price);
 
-    let token_info = pool_client.get_token_info(&position.token.clone());
-    let pool_balance = token_client.balance(&pool_contract);
+    (to_repay, fee)
+}
 
-    let token_util = token_info.total_supply.fixed_div_ceil(&env, &pool_balance, &SCALAR_7);
-    let temp_calc = token_util.fixed_mul_ceil(&env, &position.borrowed, &SCALAR_7);
-    let hourly_fee = temp_calc.fixed_mul_ceil(&env, &HOURLY_BASE_FEE, &SCALAR_7);
+/// Calculate the fee owed when closing a position at the given price, the accrued borrow fee plus the impact fee
+pub(crate) fn calculate_close_fee(env: &Env, position: &Position, current_price: i128) -> i128 {
+    calculate_borrow_fee(env, position) + calculate_impact_fee(env, position.borrowed, calculate_notional(env, &position.token, position.side, position.borrowed, current_price))
+}
 
-    let seconds_elapsed = env.ledger().timestamp() - position.timestamp;
-    let hours_elapsed = (seconds_elapsed as i128 * SCALAR_7).fixed_div_ceil(&env, &(3600 * SCALAR_7), &SCALAR_7);
+/// Build a market position opened at the current price, returning it with the impact fee charged to open it
+pub(crate) fn new_market_position(env: &Env, prices: &mut PriceContext, id: u32, input: i128, size: u32, token: Address, side: Side) -> (Position, i128) {
+    let entry_price = prices.relative_price(env, &token, entry_price_mode(side));
 
-    let mut fee = hourly_fee.fixed_mul_ceil(&env, &hours_elapsed, &SCALAR_7);
-    fee += calculate_impact_fee(&env, position.borrowed, current_price);
-    fee = fee.fixed_mul_ceil(&env, &position.borrowed, &SCALAR_7);
+    let to_borrow = input.fixed_mul_floor(env, &(size as i128), &SCALAR_7);
+    let fee = calculate_impact_fee(env, to_borrow, calculate_notional(env, &token, side, to_borrow, entry_price));
+    let position = Position {
+        id,
+        filled: true,
+        token,
+        side,
+        stop_loss: 0,
+        take_profit: 0,
+        entry_price,
+        borrowed: to_borrow,
+        leverage: size,
+        collateral: input,
+        prepaid_fee: 0,
+        keeper_fee: 0,
+        borrow_index: 0,
+        timestamp: env.ledger().timestamp(),
+    };
+    (position, fee)
+}
 
-    (to_repay, fee)
-}
\ No newline at end of file
+/// Calculate the outcome of the user closing a position at the current price.
+/// The reserved keeper fee is returned when the user closes the position themselves.
+pub(crate) fn calculate_close(env: &Env, prices: &mut PriceContext, position: &Position) -> ClosePositionQuote {
+    let price = prices.relative_price(env, &position.token, exit_price_mode(position.side));
+    let to_repay = calculate_to_repay(env, position, price);
+    let fee = calculate_close_fee(env, position, price);
+
+    ClosePositionQuote {
+        price,
+        to_repay,
+        fee,
+        payout: position.borrowed + position.collateral - to_repay - fee + position.keeper_fee,
+    }
+}
+
+/// Calculate the outcome of liquidating a position at the current price.
+/// The liquidator is paid the reserved keeper fee and its share of the liquidation fee, the pool the rest.
+pub(crate) fn calculate_liquidation(env: &Env, prices: &mut PriceContext, position: &Position) -> LiquidationQuote {
+    let price = prices.relative_price(env, &position.token, PriceMode::Spot);
+    let fee = calculate_close_fee(env, position, price);
+    let to_repay = calculate_to_repay(env, position, price);
+
+    let liquidation_fee = position.borrowed + position.collateral - to_repay;
+    let liquidation_share = storage::get_keeper_config(env, &collateral_token(env, position)).liquidation_share;
+    let keeper_share = calculate_keeper_share(env, liquidation_fee, liquidation_share);
+
+    LiquidationQuote {
+        liquidatable: is_liquidatable(env, position, price, fee),
+        price,
+        liquidation_price: calculate_liquidation_price(env, position, fee),
+        to_repay,
+        liquidation_fee: liquidation_fee - keeper_share,
+        keeper_reward: position.keeper_fee + keeper_share,
+    }
+}
+
+/// Calculate the borrow fee accrued by a position since it was opened or last settled
+pub(crate) fn calculate_borrow_fee(env: &Env, position: &Position) -> i128 {
+    let borrow_index = calculate_borrow_index(env, &collateral_token(env, position));
+    position.borrowed.fixed_mul_ceil(env, &(borrow_index - position.borrow_index), &SCALAR_12)
+}
+
+/// Calculate the hourly borrow rate of a collateral token from the share of its pool supply
+/// currently borrowed, scaled by SCALAR_7
+pub(crate) fn calculate_borrow_rate(env: &Env, token: &Address) -> i128 {
+    let pool_client = crate::dependencies::pool::Client::new(env, &storage::get_pool_contract(env));
+    let token_util = pool_client.get_utilization(token);
+    token_util.fixed_mul_ceil(env, &storage::get_fee_config(env).hourly_base_fee, &SCALAR_7)
+}
+
+/// Calculate the cumulative borrow index of a collateral token, scaled by SCALAR_12.
+/// The index grows at the rate stored by the last update, pool deposits, withdrawals and swaps since
+/// then take effect with the next borrow or repay.
+pub(crate) fn calculate_borrow_index(env: &Env, token: &Address) -> i128 {
+    let borrow_index = storage::get_borrow_index(env, token);
+    let seconds_elapsed = env.ledger().timestamp() - borrow_index.timestamp;
+    if seconds_elapsed == 0 {
+        return borrow_index.value;
+    }
+
+    let rate = borrow_index.rate * (SCALAR_12 / SCALAR_7);
+    borrow_index.value + rate.fixed_mul_ceil(env, &(seconds_elapsed as i128), &3600)
+}
+
+/// Accrue the cumulative borrow index of a collateral token up to now at the stored rate, then store
+/// the rate for the current utilization. Must be called after the amount of the token borrowed from the pool changes.
+pub(crate) fn update_borrow_index(env: &Env, token: &Address) -> i128 {
+    let value = calculate_borrow_index(env, token);
+    storage::set_borrow_index(env, token, &BorrowIndex {
+        value,
+        rate: calculate_borrow_rate(env, token),
+        timestamp: env.ledger().timestamp(),
+    });
+    value
+}
+
+/// Calculate the size in the traded token and the entry notional in the quote token of a borrow
+/// opened at `entry_price`, each in its token's decimals. Longs borrow the traded token, shorts
+/// borrow the quote token.
+pub(crate) fn calculate_open_interest(env: &Env, token: &Address, side: Side, borrowed: i128, entry_price: i128) -> (i128, i128) {
+    let quote_token = storage::get_quote_token(env);
+    match side {
+        Side::Long => {
+            let notional = borrowed.fixed_mul_floor(env, &entry_price, &SCALAR_7);
+            (borrowed, convert_decimals(env, notional, token, &quote_token))
+        }
+        Side::Short => {
+            let size = borrowed.fixed_div_floor(env, &entry_price, &SCALAR_7);
+            (convert_decimals(env, size, &quote_token, token), borrowed)
+        }
+    }
+}
+
+/// Add a borrow opened at `entry_price` to the open interest of its traded token
+pub(crate) fn increase_open_interest(env: &Env, token: &Address, side: Side, borrowed: i128, entry_price: i128) {
+    let (size, notional) = calculate_open_interest(env, token, side, borrowed, entry_price);
+    let mut open_interest = storage::get_open_interest(env, token);
+    match side {
+        Side::Long => {
+            open_interest.long_size += size;
+            open_interest.long_notional += notional;
+        }
+        Side::Short => {
+            open_interest.short_size += size;
+            open_interest.short_notional += notional;
+        }
+    }
+    storage::set_open_interest(env, token, &open_interest);
+}
+
+/// Remove a borrow opened at `entry_price` from the open interest of its traded token
+pub(crate) fn decrease_open_interest(env: &Env, token: &Address, side: Side, borrowed: i128, entry_price: i128) {
+    let (size, notional) = calculate_open_interest(env, token, side, borrowed, entry_price);
+    let mut open_interest = storage::get_open_interest(env, token);
+    // Increased positions average their entry price, so rounding can leave their size and notional
+    // slightly off the totals. Never go below zero.
+    match side {
+        Side::Long => {
+            open_interest.long_size = (open_interest.long_size - size).max(0);
+            open_interest.long_notional = (open_interest.long_notional - notional).max(0);
+        }
+        Side::Short => {
+            open_interest.short_size = (open_interest.short_size - size).max(0);
+            open_interest.short_notional = (open_interest.short_notional - notional).max(0);
+        }
+    }
+    storage::set_open_interest(env, token, &open_interest);
+}
+
+/// Calculate the unrealized PnL of the open interest of a token at the given price, in the quote token.
+/// Longs gain as the price rises above their average entry, shorts as it falls below it.
+pub(crate) fn calculate_open_interest_pnl(env: &Env, token: &Address, open_interest: &OpenInterest, current_price: i128) -> i128 {
+    let quote_token = storage::get_quote_token(env);
+    let long_value = convert_decimals(env, open_interest.long_size.fixed_mul_floor(env, &current_price, &SCALAR_7), token, &quote_token);
+    let short_value = convert_decimals(env, open_interest.short_size.fixed_mul_ceil(env, &current_price, &SCALAR_7), token, &quote_token);
+    let long_pnl = long_value - open_interest.long_notional;
+    let short_pnl = open_interest.short_notional - short_value;
+    long_pnl + short_pnl
+}
+
+/// Calculate the unrealized PnL of all open positions, valued at the oracle TWAP the pool prices SLP at.
+/// A positive PnL is owed by the pool to traders, a negative PnL is owed by traders to the pool.
+pub(crate) fn calculate_global_pnl(env: &Env, prices: &mut PriceContext) -> i128 {
+    let mut pnl = 0;
+    for token in storage::get_open_interest_tokens(env).iter() {
+        let open_interest = storage::get_open_interest(env, &token);
+        if open_interest.long_size == 0 && open_interest.short_size == 0 {
+            continue;
+        }
+        let current_price = prices.relative_price(env, &token, PriceMode::Twap);
+        pnl += calculate_open_interest_pnl(env, &token, &open_interest, current_price);
+    }
+    if pnl == 0 {
+        return 0;
+    }
+
+    let quote_token = storage::get_quote_token(env);
+    let quote_price = prices.price(env, &quote_token, PriceMode::Twap);
+    pnl.fixed_mul_floor(env, &quote_price, &10i128.pow(load_token_decimals(env, &quote_token)))
+}
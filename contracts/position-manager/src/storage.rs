@@ -1,36 +1,152 @@
 use core::iter::TakeWhile;
-use soroban_sdk::{contracttype, Address, Env};
+use soroban_sdk::{contracttype, Address, Env, Vec};
 use soroban_sdk::unwrap::UnwrapOptimized;
+use crate::constants::{BASE_FEE, DECIMALS_7, DEFAULT_MAX_PRICE_AGE, DEFAULT_MAX_PRICE_DEVIATION, DEFAULT_TWAP_RECORDS, HOURLY_BASE_FEE, IMPACT_FEE_SCALAR, MAX_LEVERAGE, MIN_LEVERAGE};
 
 const ONE_DAY_LEDGERS: u32 = 17280; // assumes 5s a ledger
 
 const LEDGER_THRESHOLD_INSTANCE: u32 = ONE_DAY_LEDGERS * 30; // ~ 30 days
 const LEDGER_BUMP_INSTANCE: u32 = LEDGER_THRESHOLD_INSTANCE + ONE_DAY_LEDGERS; // ~ 31 days
 
+const LEDGER_THRESHOLD_USER: u32 = ONE_DAY_LEDGERS * 100; // ~ 100 days
+const LEDGER_BUMP_USER: u32 = LEDGER_THRESHOLD_USER + 20 * ONE_DAY_LEDGERS; // ~ 120 days
+
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
+    Admin,
     Oracle,
     PoolContract,
-    TokenA,
-    TokenB,
-    Position(Address), // User's address as the key
+    QuoteToken,
+    Position(Address, u32), // User's address and position id as the key
+    PositionCounter(Address),
+    UserPositions(Address),
+    PositionLimits(Address),
+    KeeperConfig(Address),
+    BorrowIndex(Address),
+    FeeConfig,
+    OpenInterest(Address),
+    OpenInterestTokens,
+    PriceConfig(Address),
+    OracleDecimals,
+    TokenDecimals(Address),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[contracttype]
+pub enum Side {
+    Long,
+    Short,
+}
+
+/// The part of a position to close, either a fraction of it scaled by SCALAR_7 or an amount of its collateral
+#[derive(Clone)]
+#[contracttype]
+pub enum DecreaseAmount {
+    Fraction(i128),
+    Collateral(i128),
 }
 
 #[derive(Clone)]
 #[contracttype]
 pub struct Position {
+    pub id: u32,
     pub filled: bool,
     pub token: Address,
+    pub side: Side,
     pub entry_price: i128,
     pub stop_loss: i128,
     pub take_profit: i128,
     pub borrowed: i128,
     pub collateral: i128,
+    pub prepaid_fee: i128,
+    pub keeper_fee: i128,
+    pub borrow_index: i128,
     pub leverage: u32,
     pub timestamp: u64,
 }
 
+#[derive(Clone)]
+#[contracttype]
+pub struct PositionLimits {
+    pub min_leverage: u32,
+    pub max_leverage: u32,
+    pub min_collateral: i128,
+    pub min_notional: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct FeeConfig {
+    pub base_fee: i128,
+    pub hourly_base_fee: i128,
+    pub impact_fee_scalar: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PriceConfig {
+    pub max_age: u64,
+    pub max_deviation: i128,
+    pub twap_records: u32,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct BorrowIndex {
+    pub value: i128,
+    pub rate: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct KeeperConfig {
+    pub fill_share: i128,
+    pub liquidation_share: i128,
+    pub min_keeper_fee: i128,
+}
+
+/// The open longs and shorts of a traded token, their size in the traded token and their entry
+/// notional, the size times the entry price, in the quote token
+#[derive(Clone)]
+#[contracttype]
+pub struct OpenInterest {
+    pub long_size: i128,
+    pub long_notional: i128,
+    pub short_size: i128,
+    pub short_notional: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OpenPositionQuote {
+    pub entry_price: i128,
+    pub borrowed: i128,
+    pub fee: i128,
+    pub liquidation_price: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct ClosePositionQuote {
+    pub price: i128,
+    pub to_repay: i128,
+    pub fee: i128,
+    pub payout: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct LiquidationQuote {
+    pub liquidatable: bool,
+    pub price: i128,
+    pub liquidation_price: i128,
+    pub to_repay: i128,
+    pub liquidation_fee: i128,
+    pub keeper_reward: i128,
+}
+
 /// Bump the instance rent for the contract
 pub fn extend_instance(env: &Env) {
     env.storage()
@@ -43,6 +159,19 @@ pub fn is_init(e: &Env) -> bool {
     e.storage().instance().has(&DataKey::PoolContract)
 }
 
+/// Fetch the current admin Address
+pub fn get_admin(env: &Env) -> Address {
+    env.storage().instance().get(&DataKey::Admin).unwrap_optimized()
+}
+
+/// Set a new admin
+///
+/// ### Arguments
+/// * `new_admin` - The Address for the admin
+pub fn set_admin(env: &Env, new_admin: &Address) {
+    env.storage().instance().set(&DataKey::Admin, new_admin);
+}
+
 /// Fetch the current oracle Address
 pub fn get_oracle(env: &Env) -> Address {
     env.storage().instance().get(&DataKey::Oracle).unwrap_optimized()
@@ -56,6 +185,36 @@ pub fn set_oracle(env: &Env, address: &Address) {
     env.storage().instance().set(&DataKey::Oracle, address);
 }
 
+/// Fetch the number of decimals of the oracle's prices
+pub fn get_oracle_decimals(env: &Env) -> u32 {
+    env.storage().instance().get(&DataKey::OracleDecimals).unwrap_or(DECIMALS_7)
+}
+
+/// Set the number of decimals of the oracle's prices
+///
+/// ### Arguments
+/// * `decimals` - The decimals reported by the oracle
+pub fn set_oracle_decimals(env: &Env, decimals: u32) {
+    env.storage().instance().set(&DataKey::OracleDecimals, &decimals);
+}
+
+/// Fetch the cached number of decimals of a token, if it was loaded before
+///
+/// ### Arguments
+/// * `token` - The Address of the token
+pub fn get_token_decimals(env: &Env, token: &Address) -> Option<u32> {
+    env.storage().instance().get(&DataKey::TokenDecimals(token.clone()))
+}
+
+/// Cache the number of decimals of a token
+///
+/// ### Arguments
+/// * `token` - The Address of the token
+/// * `decimals` - The decimals reported by the token
+pub fn set_token_decimals(env: &Env, token: &Address, decimals: u32) {
+    env.storage().instance().set(&DataKey::TokenDecimals(token.clone()), &decimals);
+}
+
 /// Fetch the current pool contract Address
 pub fn get_pool_contract(env: &Env) -> Address {
     env.storage().instance().get(&DataKey::PoolContract).unwrap_optimized()
@@ -73,53 +232,234 @@ pub fn set_pool_contract(env: &Env, address: &Address) {
 ///
 /// ### Arguments
 /// * `user` - The Address of the user
-pub fn get_position(env: &Env, user: &Address) -> Position {
-    env.storage().instance().get(&DataKey::Position(user.clone())).unwrap_optimized()
+/// * `id` - The id of the position
+pub fn get_position(env: &Env, user: &Address, id: u32) -> Position {
+    let key = DataKey::Position(user.clone(), id);
+    let position = env.storage().persistent().get(&key).unwrap_optimized();
+    env.storage()
+        .persistent()
+        .extend_ttl(&key, LEDGER_THRESHOLD_USER, LEDGER_BUMP_USER);
+    position
 }
 
-pub fn has_position(env: &Env, user: &Address) -> bool {
-    env.storage().instance().has(&DataKey::Position(user.clone()))
+pub fn has_position(env: &Env, user: &Address, id: u32) -> bool {
+    env.storage().persistent().has(&DataKey::Position(user.clone(), id))
 }
 
 /// Set a user's position
 ///
 /// ### Arguments
 /// * `user` - The Address of the user
-/// * `position` - The Position to set
+/// * `position` - The Position to set, keyed by its id
 pub fn set_position(env: &Env, user: &Address, position: &Position) {
-    env.storage().instance().set(&DataKey::Position(user.clone()), position);
+    let key = DataKey::Position(user.clone(), position.id);
+    env.storage().persistent().set(&key, position);
+    env.storage()
+        .persistent()
+        .extend_ttl(&key, LEDGER_THRESHOLD_USER, LEDGER_BUMP_USER);
+
+    let mut ids = get_user_positions(env, user);
+    if !ids.contains(position.id) {
+        ids.push_back(position.id);
+        set_user_positions(env, user, &ids);
+    }
 }
 
 /// Remove a user's position
 ///
 /// ### Arguments
 /// * `user` - The Address of the user
-pub fn remove_position(env: &Env, user: &Address) {
-    env.storage().instance().remove(&DataKey::Position(user.clone()));
+/// * `id` - The id of the position
+pub fn remove_position(env: &Env, user: &Address, id: u32) {
+    env.storage().persistent().remove(&DataKey::Position(user.clone(), id));
+
+    let mut ids = get_user_positions(env, user);
+    if let Some(index) = ids.first_index_of(id) {
+        ids.remove(index);
+    }
+    if ids.is_empty() {
+        env.storage().persistent().remove(&DataKey::UserPositions(user.clone()));
+    } else {
+        set_user_positions(env, user, &ids);
+    }
+}
+
+/// Fetch the ids of all open positions of a user
+///
+/// ### Arguments
+/// * `user` - The Address of the user
+pub fn get_user_positions(env: &Env, user: &Address) -> Vec<u32> {
+    let key = DataKey::UserPositions(user.clone());
+    match env.storage().persistent().get(&key) {
+        Some(ids) => {
+            env.storage()
+                .persistent()
+                .extend_ttl(&key, LEDGER_THRESHOLD_USER, LEDGER_BUMP_USER);
+            ids
+        }
+        None => Vec::new(env),
+    }
+}
+
+fn set_user_positions(env: &Env, user: &Address, ids: &Vec<u32>) {
+    let key = DataKey::UserPositions(user.clone());
+    env.storage().persistent().set(&key, ids);
+    env.storage()
+        .persistent()
+        .extend_ttl(&key, LEDGER_THRESHOLD_USER, LEDGER_BUMP_USER);
+}
+
+/// Reserve the next position id for a user
+///
+/// ### Arguments
+/// * `user` - The Address of the user
+pub fn next_position_id(env: &Env, user: &Address) -> u32 {
+    let key = DataKey::PositionCounter(user.clone());
+    let id: u32 = env.storage().instance().get(&key).unwrap_or(0);
+    env.storage().instance().set(&key, &(id + 1));
+    id
+}
+
+/// Fetch the quote token Address
+pub fn get_quote_token(env: &Env) -> Address {
+    env.storage().instance().get(&DataKey::QuoteToken).unwrap_optimized()
+}
+
+/// Set the quote token
+///
+/// ### Arguments
+/// * `address` - The Address for the quote token
+pub fn set_quote_token(env: &Env, address: &Address) {
+    env.storage().instance().set(&DataKey::QuoteToken, address);
+}
+
+/// Fetch the position limits for a collateral token, falling back to the protocol defaults
+///
+/// ### Arguments
+/// * `token` - The Address of the collateral token
+pub fn get_position_limits(env: &Env, token: &Address) -> PositionLimits {
+    env.storage().instance().get(&DataKey::PositionLimits(token.clone())).unwrap_or(PositionLimits {
+        min_leverage: MIN_LEVERAGE as u32,
+        max_leverage: MAX_LEVERAGE as u32,
+        min_collateral: 1,
+        min_notional: 0,
+    })
 }
 
-/// Fetch the current token A Address
-pub fn get_token_a(env: &Env) -> Address {
-    env.storage().instance().get(&DataKey::TokenA).unwrap_optimized()
+/// Set the position limits for a collateral token
+///
+/// ### Arguments
+/// * `token` - The Address of the collateral token
+/// * `limits` - The PositionLimits to set
+pub fn set_position_limits(env: &Env, token: &Address, limits: &PositionLimits) {
+    env.storage().instance().set(&DataKey::PositionLimits(token.clone()), limits);
+}
+
+/// Fetch the keeper configuration for a collateral token, defaulting to no keeper rewards
+///
+/// ### Arguments
+/// * `token` - The Address of the collateral token
+pub fn get_keeper_config(env: &Env, token: &Address) -> KeeperConfig {
+    env.storage().instance().get(&DataKey::KeeperConfig(token.clone())).unwrap_or(KeeperConfig {
+        fill_share: 0,
+        liquidation_share: 0,
+        min_keeper_fee: 0,
+    })
+}
+
+/// Set the keeper configuration for a collateral token
+///
+/// ### Arguments
+/// * `token` - The Address of the collateral token
+/// * `config` - The KeeperConfig to set
+pub fn set_keeper_config(env: &Env, token: &Address, config: &KeeperConfig) {
+    env.storage().instance().set(&DataKey::KeeperConfig(token.clone()), config);
+}
+
+/// Fetch the cumulative borrow index of a collateral token
+///
+/// ### Arguments
+/// * `token` - The Address of the collateral token
+pub fn get_borrow_index(env: &Env, token: &Address) -> BorrowIndex {
+    env.storage().instance().get(&DataKey::BorrowIndex(token.clone())).unwrap_or(BorrowIndex {
+        value: 0,
+        rate: 0,
+        timestamp: env.ledger().timestamp(),
+    })
 }
 
-/// Set a new token A
+/// Set the cumulative borrow index of a collateral token
 ///
 /// ### Arguments
-/// * `address` - The Address for token A
-pub fn set_token_a(env: &Env, address: &Address) {
-    env.storage().instance().set(&DataKey::TokenA, address);
+/// * `token` - The Address of the collateral token
+/// * `index` - The BorrowIndex to set
+pub fn set_borrow_index(env: &Env, token: &Address, index: &BorrowIndex) {
+    env.storage().instance().set(&DataKey::BorrowIndex(token.clone()), index);
 }
 
-/// Fetch the current token B Address
-pub fn get_token_b(env: &Env) -> Address {
-    env.storage().instance().get(&DataKey::TokenB).unwrap_optimized()
+/// Fetch the fee configuration, falling back to the protocol defaults
+pub fn get_fee_config(env: &Env) -> FeeConfig {
+    env.storage().instance().get(&DataKey::FeeConfig).unwrap_or(FeeConfig {
+        base_fee: BASE_FEE,
+        hourly_base_fee: HOURLY_BASE_FEE,
+        impact_fee_scalar: IMPACT_FEE_SCALAR,
+    })
 }
 
-/// Set a new token B
+/// Set the fee configuration
 ///
 /// ### Arguments
-/// * `address` - The Address for token B
-pub fn set_token_b(env: &Env, address: &Address) {
-    env.storage().instance().set(&DataKey::TokenB, address);
-}
\ No newline at end of file
+/// * `config` - The FeeConfig to set
+pub fn set_fee_config(env: &Env, config: &FeeConfig) {
+    env.storage().instance().set(&DataKey::FeeConfig, config);
+}
+
+/// Fetch the oracle limits for an asset, defaulting to DEFAULT_MAX_PRICE_AGE and DEFAULT_MAX_PRICE_DEVIATION
+///
+/// ### Arguments
+/// * `asset` - The Address of the asset
+pub fn get_price_config(env: &Env, asset: &Address) -> PriceConfig {
+    env.storage().instance().get(&DataKey::PriceConfig(asset.clone())).unwrap_or(PriceConfig {
+        max_age: DEFAULT_MAX_PRICE_AGE,
+        max_deviation: DEFAULT_MAX_PRICE_DEVIATION,
+        twap_records: DEFAULT_TWAP_RECORDS,
+    })
+}
+
+/// Set the oracle limits for an asset
+///
+/// ### Arguments
+/// * `asset` - The Address of the asset
+/// * `config` - The PriceConfig to set
+pub fn set_price_config(env: &Env, asset: &Address, config: &PriceConfig) {
+    env.storage().instance().set(&DataKey::PriceConfig(asset.clone()), config);
+}
+
+/// Fetch the aggregate open interest of a traded token
+pub fn get_open_interest(env: &Env, token: &Address) -> OpenInterest {
+    env.storage().instance().get(&DataKey::OpenInterest(token.clone())).unwrap_or(OpenInterest {
+        long_size: 0,
+        long_notional: 0,
+        short_size: 0,
+        short_notional: 0,
+    })
+}
+
+/// Set the aggregate open interest of a traded token, tracking the token if it is new
+///
+/// ### Arguments
+/// * `token` - The Address of the traded token
+/// * `open_interest` - The OpenInterest to set
+pub fn set_open_interest(env: &Env, token: &Address, open_interest: &OpenInterest) {
+    let mut tokens = get_open_interest_tokens(env);
+    if !tokens.contains(token) {
+        tokens.push_back(token.clone());
+        env.storage().instance().set(&DataKey::OpenInterestTokens, &tokens);
+    }
+    env.storage().instance().set(&DataKey::OpenInterest(token.clone()), open_interest);
+}
+
+/// Fetch the tokens that have ever had open interest
+pub fn get_open_interest_tokens(env: &Env) -> Vec<Address> {
+    env.storage().instance().get(&DataKey::OpenInterestTokens).unwrap_or(Vec::new(env))
+}
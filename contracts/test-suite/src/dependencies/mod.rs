@@ -1,4 +1,5 @@
 pub mod pool;
 pub mod position_manager;
 pub mod token;
-pub mod oracle;
\ No newline at end of file
+pub mod oracle;
+pub mod oracle_aggregator;
\ No newline at end of file
@@ -0,0 +1,13 @@
+use soroban_sdk::{testutils::Address as _, Address, Env};
+
+mod oracle_aggregator_contract {
+    soroban_sdk::contractimport!(file = "../wasms/oracle_aggregator.wasm");
+}
+
+pub use oracle_aggregator_contract::{AggregatorConfig, Asset as AggregatorAsset, Client as OracleAggregatorClient, WASM as ORACLE_AGGREGATOR_WASM};
+
+pub fn create_oracle_aggregator<'a>(e: &Env) -> (Address, OracleAggregatorClient<'a>) {
+    let contract_id = Address::generate(e);
+    e.register_contract_wasm(&contract_id, ORACLE_AGGREGATOR_WASM);
+    (contract_id.clone(), OracleAggregatorClient::new(e, &contract_id))
+}
@@ -4,7 +4,7 @@ mod pool_contract {
     soroban_sdk::contractimport!(file = "../wasms/pool.wasm");
 }
 
-pub use pool_contract::{Client as PoolClient, TokenInfo, WASM as POOL_WASM};
+pub use pool_contract::{Client as PoolClient, DepositQuote, PriceConfig, TokenInfo, WithdrawQuote, WASM as POOL_WASM};
 
 pub fn create_pool<'a>(e: &Env) -> (Address, PoolClient<'a>) {
     let contract_id = Address::generate(e);
@@ -4,7 +4,7 @@ mod pool_contract {
     soroban_sdk::contractimport!(file = "../wasms/position_manager.wasm");
 }
 
-pub use pool_contract::{Client as PositionManagerClient, WASM as POSITION_MANAGER_WASM};
+pub use pool_contract::{Client as PositionManagerClient, ClosePositionQuote, DecreaseAmount, FeeConfig, KeeperConfig, LiquidationQuote, OpenInterest, OpenPositionQuote, Position, PositionLimits, PriceConfig, Side, WASM as POSITION_MANAGER_WASM};
 
 pub fn create_position_manager<'a>(e: &Env) -> (Address, PositionManagerClient<'a>) {
     let contract_id = Address::generate(e);
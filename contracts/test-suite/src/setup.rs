@@ -1,5 +1,5 @@
 use crate::test_fixture::{SCALAR_7, TestFixture, TokenIndex};
-use soroban_sdk::{log, testutils::{Address as _, Logs}, Address};
+use soroban_sdk::{log, testutils::{Address as _, Logs}, vec, Address};
 
 pub fn create_fixture_with_data<'a>() -> TestFixture<'a> {
     let fixture = TestFixture::create();
@@ -9,7 +9,7 @@ pub fn create_fixture_with_data<'a>() -> TestFixture<'a> {
     fixture.tokens[TokenIndex::XLM].mint(&frodo, &(10_000_000_000 * SCALAR_7)); // 10B XLM
     fixture.tokens[TokenIndex::USDC].mint(&frodo, &(10_000_000_000 * SCALAR_7)); // 10B USDC
 
-    fixture.pool.deposit(&frodo, &(1_000 * SCALAR_7), &(10_000 * SCALAR_7));
+    fixture.pool.deposit(&frodo, &vec![&fixture.env, 1_000 * SCALAR_7, 10_000 * SCALAR_7], &0);
     fixture
 }
 
@@ -89,14 +89,16 @@ impl TestFixture<'_> {
             address: usdc_id.clone(),
             target_ratio: (SCALAR_7 / 2) as u32,
             total_supply: 0,
+            reserved: 0,
         };
         let token_b = TokenInfo {
             address: xlm_id.clone(),
             target_ratio: (SCALAR_7 / 2) as u32,
             total_supply: 0,
+            reserved: 0,
         };
-        pool_client.initialize(&admin, &mock_oracle_id, &position_manager_id, &slp_id, &token_a, &token_b);
-        position_manager_client.initialize(&pool_id, &mock_oracle_id, &usdc_id, &xlm_id);
+        pool_client.initialize(&admin, &mock_oracle_id, &position_manager_id, &slp_id, &svec![&env, token_a, token_b]);
+        position_manager_client.initialize(&admin, &pool_id, &mock_oracle_id, &usdc_id);
 
         let fixture = TestFixture {
             env,
@@ -0,0 +1,172 @@
+#![cfg(test)]
+
+use sep_40_oracle::testutils::{Asset, MockPriceOracleClient};
+use sep_40_oracle::PriceFeedClient;
+use soroban_sdk::{testutils::Address as AddressTestTrait, vec, Address, Symbol, Vec};
+use test_suite::create_fixture_with_data;
+use test_suite::dependencies::oracle::create_mock_oracle;
+use test_suite::dependencies::oracle_aggregator::{create_oracle_aggregator, AggregatorAsset, AggregatorConfig, OracleAggregatorClient};
+use test_suite::test_fixture::{TestFixture, SCALAR_7, TokenIndex};
+
+/// Create an aggregator over one mock oracle per entry of `decimals`, each quoting USDC at $1 and XLM at $0.10
+fn create_aggregator<'a>(fixture: &TestFixture<'a>, decimals: &[u32], quorum: u32) -> (OracleAggregatorClient<'a>, std::vec::Vec<MockPriceOracleClient<'a>>) {
+    let env = &fixture.env;
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    let mut sources = Vec::new(env);
+    let mut clients = std::vec::Vec::new();
+    for source_decimals in decimals {
+        let (source_id, source) = create_mock_oracle(env);
+        source.set_data(
+            &fixture.admin,
+            &Asset::Other(Symbol::new(env, "USD")),
+            &vec![env, Asset::Stellar(usdc.clone()), Asset::Stellar(xlm.clone())],
+            source_decimals,
+            &300,
+        );
+        let scalar = 10i128.pow(*source_decimals);
+        source.set_price_stable(&vec![env, scalar, scalar / 10]);
+        sources.push_back(source_id);
+        clients.push(source);
+    }
+
+    let (_, aggregator) = create_oracle_aggregator(env);
+    aggregator.initialize(
+        &fixture.admin,
+        &AggregatorAsset::Other(Symbol::new(env, "USD")),
+        &vec![env, AggregatorAsset::Stellar(usdc), AggregatorAsset::Stellar(xlm)],
+        &7,
+        &sources,
+        &AggregatorConfig { quorum, max_age: 600, max_spread: 0_0500000 },
+    );
+    (aggregator, clients)
+}
+
+#[test]
+fn test_median_price() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let xlm = Asset::Stellar(fixture.tokens[TokenIndex::XLM].address.clone());
+    let (aggregator, sources) = create_aggregator(&fixture, &[7, 14, 7], 2);
+    let price_feed = PriceFeedClient::new(env, &aggregator.address);
+    assert_eq!(price_feed.decimals(), 7);
+    assert_eq!(price_feed.assets().len(), 2);
+
+    // The 14 decimal source reports the median, normalized to 7 decimals
+    sources[0].set_price_stable(&vec![env, 1_0000000, 0_1000000]);
+    sources[1].set_price_stable(&vec![env, 1_00000000000000, 0_10200000000000]);
+    sources[2].set_price_stable(&vec![env, 1_0000000, 0_1010000]);
+    let price_data = price_feed.lastprice(&xlm).unwrap();
+    assert_eq!(price_data.price, 0_1010000);
+    assert_eq!(price_data.timestamp, env.ledger().timestamp());
+
+    // A stale source is left out, and the median of two prices is their average
+    sources[2].set_price(&vec![env, 1_0000000, 0_1010000], &(env.ledger().timestamp() - 3600));
+    assert_eq!(price_feed.lastprice(&xlm).unwrap().price, 0_1010000);
+
+    // An asset the aggregator does not quote has no price
+    let unknown = Asset::Stellar(Address::generate(env));
+    assert!(price_feed.lastprice(&unknown).is_none());
+}
+
+#[test]
+fn test_quorum_not_reached() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let xlm = Asset::Stellar(fixture.tokens[TokenIndex::XLM].address.clone());
+    let (aggregator, sources) = create_aggregator(&fixture, &[7, 7, 7], 2);
+
+    // Two of the three sources halt, leaving a single fresh price
+    let halted_at = env.ledger().timestamp() - 3600;
+    sources[0].set_price(&vec![env, 1_0000000, 0_1000000], &halted_at);
+    sources[1].set_price(&vec![env, 1_0000000, 0_1000000], &halted_at);
+    let feed = PriceFeedClient::new(env, &aggregator.address);
+    assert!(feed.lastprice(&xlm).is_none());
+    assert!(feed.prices(&xlm, &3).is_none());
+}
+
+#[test]
+fn test_prices_aligned_by_timestamp() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let xlm = Asset::Stellar(fixture.tokens[TokenIndex::XLM].address.clone());
+    let (aggregator, sources) = create_aggregator(&fixture, &[7, 7, 7], 2);
+    let now = env.ledger().timestamp();
+
+    // Two sources have a record one resolution back, only one has a record two resolutions back
+    sources[0].set_price(&vec![env, 1_0000000, 0_0800000], &(now - 600));
+    sources[0].set_price(&vec![env, 1_0000000, 0_0900000], &(now - 300));
+    sources[1].set_price(&vec![env, 1_0000000, 0_0900000], &(now - 300));
+    sources[0].set_price(&vec![env, 1_0000000, 0_1000000], &now);
+    sources[1].set_price(&vec![env, 1_0000000, 0_1000000], &now);
+
+    // The timestamp below the quorum is left out
+    let prices = PriceFeedClient::new(env, &aggregator.address).prices(&xlm, &3).unwrap();
+    assert_eq!(prices.len(), 2);
+    assert_eq!(prices.get_unchecked(0).timestamp, now);
+    assert_eq!(prices.get_unchecked(0).price, 0_1000000);
+    assert_eq!(prices.get_unchecked(1).timestamp, now - 300);
+    assert_eq!(prices.get_unchecked(1).price, 0_0900000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #704)")]
+fn test_price_disagreement() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let xlm = Asset::Stellar(fixture.tokens[TokenIndex::XLM].address.clone());
+    let (aggregator, sources) = create_aggregator(&fixture, &[7, 7, 7], 2);
+
+    // One source reports XLM at $0.20, 100% above the $0.10 median and beyond the 5% maximum spread
+    sources[2].set_price_stable(&vec![env, 1_0000000, 0_2000000]);
+    PriceFeedClient::new(env, &aggregator.address).lastprice(&xlm);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #703)")]
+fn test_set_config_invalid() {
+    let fixture = create_fixture_with_data();
+    let (aggregator, _) = create_aggregator(&fixture, &[7, 7], 1);
+
+    // The quorum cannot exceed the number of sources
+    aggregator.set_config(&AggregatorConfig { quorum: 3, max_age: 600, max_spread: 0_0500000 });
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #702)")]
+fn test_set_sources_mismatched_resolution() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let (aggregator, _) = create_aggregator(&fixture, &[7, 7], 1);
+
+    // Records are combined by position, so a source updating every minute cannot join 5 minute sources
+    let (source_id, source) = create_mock_oracle(env);
+    source.set_data(
+        &fixture.admin,
+        &Asset::Other(Symbol::new(env, "USD")),
+        &vec![env, Asset::Stellar(fixture.tokens[TokenIndex::XLM].address.clone())],
+        &7,
+        &60,
+    );
+    let mut sources = aggregator.get_sources();
+    sources.push_back(source_id);
+    aggregator.set_sources(&sources);
+}
+
+#[test]
+fn test_pool_with_aggregator() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let frodo = fixture.users[0].clone();
+    let (aggregator, _) = create_aggregator(&fixture, &[7, 14, 7], 2);
+
+    // The pool reads the aggregator through the SEP-40 interface like any other oracle
+    fixture.pool.set_oracle(&aggregator.address);
+    fixture.tokens[TokenIndex::USDC].mint(&frodo, &(100 * SCALAR_7));
+    fixture.tokens[TokenIndex::XLM].mint(&frodo, &(1_000 * SCALAR_7));
+
+    // $200 deposited into the balanced pool is charged only the 0.3% base fee
+    let minted = fixture.pool.deposit(&frodo, &vec![env, 100 * SCALAR_7, 1_000 * SCALAR_7], &0);
+    assert_eq!(minted, 199_4000000);
+}
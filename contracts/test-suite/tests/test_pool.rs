@@ -1,11 +1,16 @@
 #![cfg(test)]
 
 use soroban_sdk::{
-    log, testutils::{Address as AddressTestTrait, Events, Logs}, vec, Address, Error, IntoVal, Symbol, Val, Vec
+    testutils::{Address as AddressTestTrait, Events}, vec, Address, Error, IntoVal, Symbol, Val, Vec
 };
 use test_suite::create_fixture_with_data;
 use test_suite::test_fixture::{SCALAR_7, TokenIndex};
 use test_suite::assertions::assert_approx_eq_abs;
+use test_suite::dependencies::position_manager::{create_position_manager, Side};
+use test_suite::dependencies::pool::{create_pool, PriceConfig, TokenInfo};
+use test_suite::dependencies::oracle::create_mock_oracle;
+use test_suite::dependencies::token::{create_stellar_token, create_token};
+use sep_40_oracle::testutils::Asset;
 
 #[test]
 fn test_pool() {
@@ -17,10 +22,11 @@ fn test_pool() {
     fixture.tokens[TokenIndex::USDC].mint(&henk, &(1_000 * SCALAR_7));
 
     // Initial deposit
-    fixture.pool.deposit(&henk, &(1_000 * SCALAR_7), &(10_000 * SCALAR_7));
+    fixture.pool.deposit(&henk, &vec![&fixture.env, 1_000 * SCALAR_7, 10_000 * SCALAR_7], &0);
 
     // Henk deposited 10,000 XLM (1,000 USD) and 1,000 USDC (1,000 USD), total $2,000
-    assert_eq!(fixture.tokens[TokenIndex::SLP].balance(&henk), 2_000 * SCALAR_7);
+    // The deposit keeps the pool balanced so only the 0.3% base fee is charged, $2,000 * 0.997 = $1,994
+    assert_eq!(fixture.tokens[TokenIndex::SLP].balance(&henk), 1_994 * SCALAR_7);
 
     // Confirm pool's balance after deposit
     // Initial deposit was 10,000 XLM and 1,000 USDC
@@ -39,34 +45,35 @@ fn test_pool() {
     // The ratio is 1,800 / 3,800 = 47.37% for XLM and 2,000 / 3,800 = 52.63% for USDC
     // To correct the ratio we need $200 worth of XLM, that is $200 / 0.09 = 2,222.22 XLM
     fixture.tokens[TokenIndex::XLM].mint(&henk, &(2_222 * SCALAR_7));
-    fixture.pool.deposit(&henk, &0, &(2_222 * SCALAR_7));
+    fixture.pool.deposit(&henk, &vec![&fixture.env, 0, 2_222 * SCALAR_7], &0);
 
     // Calculate expected SLP tokens for the second deposit
     // The deposit value is 2,222 XLM * 0.09 = $199,98
-    // SLP tokens are 199,98 * 4,000 / 3,800 = 210,505263
-    let expected_new_slp = 210_5052631; // Rounded down
-    assert_eq!(fixture.tokens[TokenIndex::SLP].balance(&henk), 2_000 * SCALAR_7 + expected_new_slp);
+    // XLM is $100 below its $1,900 target so the deposit gets a rebate of 0.5% * 100 / 1,900 = 0.0263%
+    // The fee is 0.3% - 0.0263% = 0.2737% of $199,98 = $0,547316
+    // SLP tokens are (199,98 - 0,547316) * 3,994 / 3,800 = 209,614237
+    let expected_new_slp = 209_6142375; // Rounded down
+    assert_eq!(fixture.tokens[TokenIndex::SLP].balance(&henk), 1_994 * SCALAR_7 + expected_new_slp);
 
     // Confirm updated pool balance
     assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&fixture.pool.address), 22_222 * SCALAR_7);
     assert_eq!(fixture.tokens[TokenIndex::USDC].balance(&fixture.pool.address), 2_000 * SCALAR_7);
 
     // Henk withdraws 1,000 SLP tokens
-    let (withdrawn_usdc, withdrawn_xlm) = fixture.pool.withdraw(&henk, &(1_000 * SCALAR_7));
+    let withdrawn = fixture.pool.withdraw(&henk, &(1_000 * SCALAR_7), &vec![&fixture.env, 0, 0]);
+    let (withdrawn_usdc, withdrawn_xlm) = (withdrawn.get_unchecked(0), withdrawn.get_unchecked(1));
 
     // Expected withdraw amounts
-    // 1000 SLP tokens to withdraw total ~4210,505 SLP tokens
-    // 1000 / 4210,505 * $3999,98 = $950
-    // Pool is balanced so $950 / 2 = 475 USDC and $475 / 0.09 = 5277,777 XLM
+    // 1000 SLP tokens to withdraw total ~4203,614 SLP tokens
+    // 1000 / 4203,614 * $3999,98 = $951,56
+    // Pool is balanced so $951,56 / 2 = $475,78 of each token, less the 0.3% base fee = $474,36
+    // That is 474,36 USDC and $474,36 / 0.09 = 5270,57 XLM
 
-    fixture.env.logs().print();
-    println!("withdrawn_usdc: {} withdrawn_xlm: {}", withdrawn_usdc, withdrawn_xlm);
+    assert_approx_eq_abs(withdrawn_usdc, 474_356_0286, 1000_000);
+    assert_approx_eq_abs(withdrawn_xlm,  5270_517_1288, 1000_000);
 
-    assert_approx_eq_abs(withdrawn_usdc, 475 * SCALAR_7, 1000_000);
-    assert_approx_eq_abs(withdrawn_xlm,  5277_777_0000, 1000_000);
-
-    let new_xlm_amount = 22_222 * SCALAR_7 - 5277_777_0000;
-    let new_usdc_amount = 2_000 * SCALAR_7 - 475 * SCALAR_7;
+    let new_xlm_amount = 22_222 * SCALAR_7 - 5270_517_1288;
+    let new_usdc_amount = 2_000 * SCALAR_7 - 474_356_0286;
 
     assert_approx_eq_abs(fixture.tokens[TokenIndex::XLM].balance(&fixture.pool.address), new_xlm_amount, 1000_000);
     assert_approx_eq_abs(fixture.tokens[TokenIndex::USDC].balance(&fixture.pool.address), new_usdc_amount, 1000_000);
@@ -78,22 +85,20 @@ fn test_pool() {
         100_0000,
     ]);
 
-    let (withdrawn_usdc, withdrawn_xlm) = fixture.pool.withdraw(&henk, &(1_000 * SCALAR_7));
+    let withdrawn = fixture.pool.withdraw(&henk, &(1_000 * SCALAR_7), &vec![&fixture.env, 0, 0]);
+    let (withdrawn_usdc, withdrawn_xlm) = (withdrawn.get_unchecked(0), withdrawn.get_unchecked(1));
 
-    // The pool now has 2000 - 475 = 1525 USDC and 22222 - 5277,777 = 16944,223 XLM
-    // The pool value is 1525 + 16944,223 * 0.10 = $3219,4223
+    // The pool now has 2000 - 474,356 = 1525,644 USDC and 22222 - 5270,517 = 16951,483 XLM
+    // The pool value is 1525,644 + 16951,483 * 0.10 = $3220,792
     // Expected withdraw amounts
-    // 1000 SLP tokens to withdraw total ~3210,505 SLP tokens
-    // 1000 / 3210,505 * $3219,4223 = $1002,77754
-    // The current ratio is $1525 / $3219,4223 = 47,368747% for USDC and 52,631253% for XLM
-    // Excess XLM ratio is 52,631253% - 50% = 2,631253%
-    // The adjust factor is 2,631253% / 52,631253% = 0,04999412
-    // Value of XLM to withdraw is $1002,77754 * 0,52631253 * (1 + 0,04999412) = $554,16
-    // Value of USDC to withdraw is $1002,77754 - $554,16 = $448,61754
-    // XLM to withdraw is $554,16 / 0,10 = 5541,6 XLM
-
-    assert_approx_eq_abs(withdrawn_usdc, 448_617_0000, 1000_000);
-    assert_approx_eq_abs(withdrawn_xlm, 5541_600_0000, 1000_000);
+    // 1000 SLP tokens to withdraw total ~3203,614 SLP tokens
+    // 1000 / 3203,614 * $3220,792 = $1005,36
+    // The withdrawal is skewed towards XLM which is above its target ratio
+    // Withdrawing USDC moves it further below its target so it pays more than the base fee,
+    // withdrawing XLM moves it towards its target so it gets a rebate on the base fee
+
+    assert_approx_eq_abs(withdrawn_usdc, 448_539_5989, 1000_000);
+    assert_approx_eq_abs(withdrawn_xlm, 5540_707_5530, 1000_000);
 
     fixture.oracle.set_price_stable(&vec![
         &fixture.env,
@@ -101,30 +106,49 @@ fn test_pool() {
         80_0000,
     ]);
 
-    let (withdrawn_usdc, withdrawn_xlm) = fixture.pool.withdraw(&henk, &(200 * SCALAR_7));
+    let withdrawn = fixture.pool.withdraw(&henk, &(200 * SCALAR_7), &vec![&fixture.env, 0, 0]);
+    let (withdrawn_usdc, withdrawn_xlm) = (withdrawn.get_unchecked(0), withdrawn.get_unchecked(1));
 
-    // The pool now has 1525 - 448,61754 = 1076,38246 USDC and 16944,223 - 5541,6 = 11402,623 XLM
-    // The pool value is 1076,38246 + 11402,623 * 0.08 = $1988,5923
+    // The pool now has 1525,644 - 448,540 = 1077,104 USDC and 16951,483 - 5540,708 = 11410,775 XLM
+    // The pool value is 1077,104 + 11410,775 * 0.08 = $1989,966
     // Expected withdraw amounts
-    // 200 SLP tokens to withdraw total ~2210,505 SLP tokens
-    // 200 / 2210,505 * $1988,5923 = $179,921991
-    // The current ratio is $1076,38246 / $1988,5923 = 54,12786% for USDC and 45,87214% for XLM
-    // Excess USDC ratio is 54,12786% - 50% = 4,12786%
-    // The adjust factor is 4,12786% / 54,12786% = 0,07626128
-    // Value of USDC to withdraw is $179,921991 * 0,5412786 * (1 + 0,07626128) = $104,814851
-    // Value of XLM to withdraw is $179,921991  - $104,814851 = $75,10714
-    // XLM to withdraw is $75,10714 / 0,08 = 938,83925 XLM
-    assert_approx_eq_abs(withdrawn_usdc, 104_814_8510, 1000_000);
-    assert_approx_eq_abs(withdrawn_xlm, 938_839_2500, 1000_000);
+    // 200 SLP tokens to withdraw total ~2203,614 SLP tokens
+    // 200 / 2203,614 * $1989,966 = $180,61
+    // The withdrawal is skewed towards USDC which is now above its target ratio
+    assert_approx_eq_abs(withdrawn_usdc, 104_938_9645, 1000_000);
+    assert_approx_eq_abs(withdrawn_xlm, 940_035_9800, 1000_000);
 }
 
 #[test]
-#[should_panic(expected = "Error(Contract, #504)")]
 fn test_unbalanced_deposit() {
     let fixture = create_fixture_with_data();
     let henk = Address::generate(&fixture.env);
 
-    fixture.pool.deposit(&henk, &(1_000 * SCALAR_7), &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::XLM].mint(&henk, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(1_000 * SCALAR_7));
+
+    // Depositing $1,000 of USDC and $100 of XLM leaves both tokens $450 off their $1,550 target
+    // The deposit is accepted but pays the base fee plus a tax on the average distance to target
+    // 0.3% + 0.5% * $225 / $1,550 = 0.3726%, $1,100 * (1 - 0.003726) = $1,095,90
+    let minted = fixture.pool.deposit(&henk, &vec![&fixture.env, 1_000 * SCALAR_7, 1_000 * SCALAR_7], &0);
+    assert_eq!(minted, 1_095_9015100);
+}
+
+#[test]
+fn test_rebalancing_deposit() {
+    let fixture = create_fixture_with_data();
+    let henk = Address::generate(&fixture.env);
+
+    fixture.tokens[TokenIndex::XLM].mint(&henk, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(100 * SCALAR_7));
+
+    // A USDC only deposit leaves the pool above its USDC target
+    let minted = fixture.pool.deposit(&henk, &vec![&fixture.env, 100 * SCALAR_7, 0], &0);
+    assert_eq!(minted, 99_6880900);
+
+    // Depositing the same value of XLM moves the pool back to its targets and pays less than the base fee
+    let minted = fixture.pool.deposit(&henk, &vec![&fixture.env, 0, 1_000 * SCALAR_7], &0);
+    assert_eq!(minted, 99_7089881);
 }
 
 #[test]
@@ -137,7 +161,37 @@ fn test_balanced_deposit() {
     fixture.tokens[TokenIndex::USDC].mint(&henk, &(1_000 * SCALAR_7));
 
     // Initial deposit
-    fixture.pool.deposit(&henk, &(1_000 * SCALAR_7), &(10_000 * SCALAR_7));
+    fixture.pool.deposit(&henk, &vec![&fixture.env, 1_000 * SCALAR_7, 10_000 * SCALAR_7], &0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #512)")]
+fn test_deposit_negative_amount() {
+    let fixture = create_fixture_with_data();
+    let henk = Address::generate(&fixture.env);
+
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(1_000 * SCALAR_7));
+
+    // A negative amount would shrink the pool's USDC supply without any transfer
+    fixture.pool.deposit(&henk, &vec![&fixture.env, 1_000 * SCALAR_7, -10_000 * SCALAR_7], &0);
+}
+
+#[test]
+fn test_withdraw_zero_target_token() {
+    let fixture = create_fixture_with_data();
+    let henk = Address::generate(&fixture.env);
+
+    fixture.tokens[TokenIndex::XLM].mint(&henk, &(10_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(1_000 * SCALAR_7));
+    fixture.pool.deposit(&henk, &vec![&fixture.env, 1_000 * SCALAR_7, 10_000 * SCALAR_7], &0);
+
+    // XLM is being phased out, all of its value is now off target
+    fixture.pool.set_target_ratios(&vec![&fixture.env, SCALAR_7 as u32, 0]);
+
+    // The withdrawal is taken entirely in XLM and the rebate covers the whole base fee
+    // 1000 / 3994 * $4,000 = $1,001,502 which is 10,015,02 XLM
+    let withdrawn = fixture.pool.withdraw(&henk, &(1_000 * SCALAR_7), &vec![&fixture.env, 0, 0]);
+    assert_eq!(withdrawn, vec![&fixture.env, 0, 10_015_0200000]);
 }
 
 #[test]
@@ -147,22 +201,558 @@ fn testing() {
 
     fixture.tokens[TokenIndex::XLM].mint(&ben, &(10_000 * SCALAR_7));
 
-    fixture.position_manager.open_position(&ben, &(1_000 * SCALAR_7), &20000000, &fixture.tokens[TokenIndex::XLM].address);
+    let (id, _) = fixture.position_manager.open_position(&ben, &(1_000 * SCALAR_7), &20000000, &fixture.tokens[TokenIndex::XLM].address, &Side::Long, &i128::MAX);
 
-    let position = fixture.position_manager.get_position(&ben);
-    println!("Position: {:?}", position);
+    let position = fixture.position_manager.get_position(&ben, &id);
 
     // we supply 1000 XLM fee of 0.1% is charged
     // 1000 * 0.001 = 1 XLM fee so collateral should be 999 XLM
     assert_eq!(position.collateral, 999 * SCALAR_7);
 
-    fixture.position_manager.close_position(&ben);
+    fixture.position_manager.close_position(&ben, &id, &0);
     let balance = fixture.tokens[TokenIndex::XLM].balance(&ben);
     assert_eq!(balance, 99_980_010_000);
+}
+
+
+
+#[test]
+fn test_set_admin() {
+    let fixture = create_fixture_with_data();
+    let new_admin = Address::generate(&fixture.env);
+
+    // The admin only changes once the proposed admin accepts
+    fixture.pool.set_admin(&new_admin);
+    assert_eq!(fixture.pool.get_admin(), fixture.admin);
+
+    fixture.pool.accept_admin();
+    assert_eq!(fixture.pool.get_admin(), new_admin);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #510)")]
+fn test_accept_admin_without_proposal() {
+    let fixture = create_fixture_with_data();
+
+    fixture.pool.accept_admin();
+}
+
+#[test]
+fn test_set_oracle_and_position_manager() {
+    let fixture = create_fixture_with_data();
+    let (oracle, oracle_client) = create_mock_oracle(&fixture.env);
+    oracle_client.set_data(&fixture.admin, &Asset::Other(Symbol::new(&fixture.env, "USD")), &vec![&fixture.env], &7, &300);
+    let position_manager = Address::generate(&fixture.env);
+
+    fixture.pool.set_oracle(&oracle);
+    assert_eq!(fixture.pool.get_oracle(), oracle);
+
+    // Only the admin can point the pool at a new position manager
+    fixture.pool.set_position_manager(&position_manager);
+    assert_eq!(fixture.env.auths()[0].0, fixture.admin);
+}
+
+#[test]
+fn test_set_target_ratios() {
+    let fixture = create_fixture_with_data();
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.pool.set_target_ratios(&vec![&fixture.env, 0_7000000, 0_3000000]);
+    assert_eq!(fixture.pool.get_token_info(&usdc).target_ratio, 0_7000000);
+    assert_eq!(fixture.pool.get_token_info(&xlm).target_ratio, 0_3000000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #503)")]
+fn test_set_target_ratios_invalid() {
+    let fixture = create_fixture_with_data();
+
+    fixture.pool.set_target_ratios(&vec![&fixture.env, 0_7000000, 0_4000000]);
+}
+
+#[test]
+fn test_multi_asset_pool() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let henk = Address::generate(env);
+
+    // A pool holding USDC, XLM and BTC at 40/30/30 target ratios
+    let (btc_id, btc) = create_stellar_token(env, &fixture.admin);
+    let (slp_id, slp) = create_stellar_token(env, &fixture.admin);
+    let (oracle_id, oracle) = create_mock_oracle(env);
+    let (pool_id, pool) = create_pool(env);
+    slp.set_admin(&pool_id);
+
+    let usdc = &fixture.tokens[TokenIndex::USDC];
+    let xlm = &fixture.tokens[TokenIndex::XLM];
+    oracle.set_data(
+        &fixture.admin,
+        &Asset::Other(Symbol::new(env, "USD")),
+        &vec![env, Asset::Stellar(usdc.address.clone()), Asset::Stellar(xlm.address.clone()), Asset::Stellar(btc_id.clone())],
+        &7,
+        &300,
+    );
+    oracle.set_price_stable(&vec![env, 1_0000000, 0_1000000, 60_000_0000000]);
+
+    let tokens = vec![
+        env,
+        TokenInfo { address: usdc.address.clone(), total_supply: 0, target_ratio: 0_4000000, reserved: 0 },
+        TokenInfo { address: xlm.address.clone(), total_supply: 0, target_ratio: 0_3000000, reserved: 0 },
+        TokenInfo { address: btc_id.clone(), total_supply: 0, target_ratio: 0_3000000, reserved: 0 },
+    ];
+    pool.initialize(&fixture.admin, &oracle_id, &fixture.position_manager.address, &slp_id, &tokens);
+
+    // $400 of USDC, $300 of XLM and $300 of BTC
+    usdc.mint(&henk, &(1_000 * SCALAR_7));
+    xlm.mint(&henk, &(10_000 * SCALAR_7));
+    btc.mint(&henk, &SCALAR_7);
+    let minted = pool.deposit(&henk, &vec![env, 400 * SCALAR_7, 3_000 * SCALAR_7, 0_0050000], &0);
+    assert_eq!(minted, 1_000 * SCALAR_7);
+    assert_eq!(pool.get_token_info(&btc_id).total_supply, 0_0050000);
+
+    // A balanced pool is withdrawn pro rata, less the 0.3% base fee
+    let withdrawn = pool.withdraw(&henk, &(100 * SCALAR_7), &vec![env, 0, 0, 0]);
+    assert_eq!(withdrawn, vec![env, 39_8800000, 299_1000000, 0_0004985]);
+    assert_eq!(btc.balance(&henk), SCALAR_7 - 0_0050000 + 0_0004985);
+
+    // Depositing only USDC pushes it above its target and pays more than the base fee
+    let minted = pool.deposit(&henk, &vec![env, 100 * SCALAR_7, 0, 0], &0);
+    assert_eq!(minted, 99_6293002);
+}
+
+#[test]
+fn test_token_and_oracle_decimals() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let henk = Address::generate(env);
+
+    // A pool of a 6 decimal USDC and the 7 decimal XLM, priced by an oracle with 14 decimals
+    let (usdc_id, usdc) = create_token(env, &fixture.admin, 6, "USDC");
+    let (slp_id, slp) = create_stellar_token(env, &fixture.admin);
+    let (oracle_id, oracle) = create_mock_oracle(env);
+    let (pool_id, pool) = create_pool(env);
+    let (position_manager_id, position_manager) = create_position_manager(env);
+    slp.set_admin(&pool_id);
+
+    let xlm = &fixture.tokens[TokenIndex::XLM];
+    oracle.set_data(
+        &fixture.admin,
+        &Asset::Other(Symbol::new(env, "USD")),
+        &vec![env, Asset::Stellar(usdc_id.clone()), Asset::Stellar(xlm.address.clone())],
+        &14,
+        &300,
+    );
+    oracle.set_price_stable(&vec![env, 1_00000000000000, 0_10000000000000]);
+
+    let tokens = vec![
+        env,
+        TokenInfo { address: usdc_id.clone(), total_supply: 0, target_ratio: 0_5000000, reserved: 0 },
+        TokenInfo { address: xlm.address.clone(), total_supply: 0, target_ratio: 0_5000000, reserved: 0 },
+    ];
+    pool.initialize(&fixture.admin, &oracle_id, &position_manager_id, &slp_id, &tokens);
+    position_manager.initialize(&fixture.admin, &pool_id, &oracle_id, &usdc_id);
+
+    // $500 of USDC and $500 of XLM are valued the same as in a 7 decimal pool
+    usdc.mint(&henk, &(1_000 * 1_000000));
+    xlm.mint(&henk, &(10_000 * SCALAR_7));
+    let minted = pool.deposit(&henk, &vec![env, 500 * 1_000000, 5_000 * SCALAR_7], &0);
+    assert_eq!(minted, 1_000 * SCALAR_7);
+
+    let withdrawn = pool.withdraw(&henk, &(100 * SCALAR_7), &vec![env, 0, 0]);
+    assert_eq!(withdrawn, vec![env, 49_850000, 498_5000000]);
+
+    // 10 SLP is paid out as close to 10 USDC in USDC's own decimals
+    let withdrawn = pool.withdraw_single(&henk, &(10 * SCALAR_7), &usdc_id, &0);
+    assert_approx_eq_abs(withdrawn, 9_970000, 0_010000);
+
+    // Swapping $10 of USDC returns close to 100 XLM
+    let amount_out = pool.swap(&henk, &usdc_id, &xlm.address, &(10 * 1_000000), &0);
+    assert_approx_eq_abs(amount_out, 100 * SCALAR_7, SCALAR_7);
+
+    // A 200 XLM long opened at $0.10 has a $20 notional in USDC's decimals and is $2 in profit at $0.11
+    position_manager.open_position(&henk, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm.address, &Side::Long, &i128::MAX);
+    let open_interest = position_manager.get_open_interest(&xlm.address);
+    assert_eq!((open_interest.long_size, open_interest.long_notional), (200 * SCALAR_7, 20 * 1_000000));
+    oracle.set_price_stable(&vec![env, 1_00000000000000, 0_11000000000000]);
+    assert_eq!(position_manager.get_global_pnl(), 2 * SCALAR_7);
+    pool.withdraw(&henk, &(10 * SCALAR_7), &vec![env, 0, 0]);
+}
+
+#[test]
+fn test_withdraw_single() {
+    let fixture = create_fixture_with_data();
+    let frodo = fixture.users[0].clone();
+    let usdc_balance = fixture.tokens[TokenIndex::USDC].balance(&frodo);
+    let xlm_balance = fixture.tokens[TokenIndex::XLM].balance(&frodo);
+
+    // 100 SLP of the 2,000 SLP supply is worth $100 of the $2,000 pool
+    // Paying it all in USDC moves USDC $50 below its $950 target, averaging $25 off target
+    // The fee is 0.3% + 0.5% * 25 / 950 = 0.31316%, so 100 * (1 - 0.0031316) = 99,68684 USDC
+    let withdrawn = fixture.pool.withdraw_single(&frodo, &(100 * SCALAR_7), &fixture.tokens[TokenIndex::USDC].address, &0);
+    assert_eq!(withdrawn, 99_6868400);
+
+    assert_eq!(fixture.tokens[TokenIndex::USDC].balance(&frodo), usdc_balance + withdrawn);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&frodo), xlm_balance);
+    assert_eq!(fixture.tokens[TokenIndex::SLP].balance(&frodo), 1_900 * SCALAR_7);
+    assert_eq!(fixture.pool.get_slp_supply(), 1_900 * SCALAR_7);
+    assert_eq!(fixture.pool.get_token_info(&fixture.tokens[TokenIndex::USDC].address).total_supply, 1_000 * SCALAR_7 - withdrawn);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #505)")]
+fn test_withdraw_single_borrowed_liquidity() {
+    let fixture = create_fixture_with_data();
+    let frodo = fixture.users[0].clone();
+
+    let ben = Address::generate(&fixture.env);
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+
+    // An 8x short borrows 800 of the 1,000 USDC in the pool and pays its opening fee to it
+    let (_, fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &8_0000000, &fixture.tokens[TokenIndex::XLM].address, &Side::Short, &0);
+    assert_eq!(fixture.tokens[TokenIndex::USDC].balance(&fixture.pool.address), 200 * SCALAR_7 + fee);
+
+    // $300 of USDC can not be paid out of the remaining USDC
+    fixture.pool.withdraw_single(&frodo, &(300 * SCALAR_7), &fixture.tokens[TokenIndex::USDC].address, &0);
+}
+
+#[test]
+fn test_swap() {
+    let fixture = create_fixture_with_data();
+    let usdc = &fixture.tokens[TokenIndex::USDC];
+    let xlm = &fixture.tokens[TokenIndex::XLM];
+    let henk = Address::generate(&fixture.env);
+    usdc.mint(&henk, &(100 * SCALAR_7));
+
+    // Swapping $100 of USDC for XLM moves both tokens $100 off their $1,000 targets, averaging $50 off target
+    // The fee is 0.3% + 0.5% * 50 / 1,000 = 0.325%, so $100 * (1 - 0.00325) / 0.10 = 996,75 XLM
+    let amount_out = fixture.pool.swap(&henk, &usdc.address, &xlm.address, &(100 * SCALAR_7), &(996 * SCALAR_7));
+    assert_eq!(amount_out, 996_7500000);
+
+    assert_eq!(usdc.balance(&henk), 0);
+    assert_eq!(xlm.balance(&henk), amount_out);
+    assert_eq!(fixture.pool.get_token_info(&usdc.address).total_supply, 1_100 * SCALAR_7);
+    assert_eq!(fixture.pool.get_token_info(&xlm.address).total_supply, 10_000 * SCALAR_7 - amount_out);
+}
+
+#[test]
+fn test_rebalancing_swap() {
+    let fixture = create_fixture_with_data();
+    let usdc = &fixture.tokens[TokenIndex::USDC];
+    let xlm = &fixture.tokens[TokenIndex::XLM];
+    let henk = Address::generate(&fixture.env);
+    xlm.mint(&henk, &(500 * SCALAR_7));
+
+    // Price for XLM goes down to 0.09, the pool holds $900 of XLM and $1,000 of USDC
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_000_0000, 90_0000]);
+
+    // Swapping 500 XLM ($45) for USDC moves both tokens towards their $950 targets
+    // The fee is 0.3% - 0.5% * 50 / 950 = 0.27369%, so $45 * (1 - 0.0027369) = 44,8768395 USDC
+    let amount_out = fixture.pool.swap(&henk, &xlm.address, &usdc.address, &(500 * SCALAR_7), &0);
+    assert_eq!(amount_out, 44_8768395);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #513)")]
+fn test_swap_insufficient_amount_out() {
+    let fixture = create_fixture_with_data();
+    let usdc = &fixture.tokens[TokenIndex::USDC];
+    let xlm = &fixture.tokens[TokenIndex::XLM];
+    let henk = Address::generate(&fixture.env);
+    usdc.mint(&henk, &(100 * SCALAR_7));
+
+    fixture.pool.swap(&henk, &usdc.address, &xlm.address, &(100 * SCALAR_7), &(1_000 * SCALAR_7));
+}
+
+#[test]
+fn test_swap_out_of_zero_target_token() {
+    let fixture = create_fixture_with_data();
+    let usdc = &fixture.tokens[TokenIndex::USDC];
+    let xlm = &fixture.tokens[TokenIndex::XLM];
+    let henk = Address::generate(&fixture.env);
+    usdc.mint(&henk, &(10 * SCALAR_7));
+
+    // XLM is being phased out, swapping it out moves it towards its target of nothing
+    fixture.pool.set_target_ratios(&vec![&fixture.env, SCALAR_7 as u32, 0]);
+
+    // Both legs move towards their target, only part of the base fee is left on the USDC leg
+    let amount_out = fixture.pool.swap(&henk, &usdc.address, &xlm.address, &(10 * SCALAR_7), &0);
+    assert_eq!(amount_out, 99_9500000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #514)")]
+fn test_deposit_insufficient_slp_out() {
+    let fixture = create_fixture_with_data();
+    let henk = Address::generate(&fixture.env);
+
+    fixture.tokens[TokenIndex::XLM].mint(&henk, &(10_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(1_000 * SCALAR_7));
+
+    // The deposit mints 1,994 SLP after the base fee
+    fixture.pool.deposit(&henk, &vec![&fixture.env, 1_000 * SCALAR_7, 10_000 * SCALAR_7], &(2_000 * SCALAR_7));
+}
+
+#[test]
+fn test_withdraw_insufficient_amount_out() {
+    let fixture = create_fixture_with_data();
+    let frodo = fixture.users[0].clone();
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+
+    // Withdrawing 100 SLP pays out 49,85 USDC and 498,5 XLM after the base fee
+    let result = fixture.pool.try_withdraw(&frodo, &(100 * SCALAR_7), &vec![&fixture.env, 49 * SCALAR_7, 500 * SCALAR_7]);
+    assert_eq!(result.err(), Some(Ok(Error::from_contract_error(515))));
+
+    let result = fixture.pool.try_withdraw_single(&frodo, &(100 * SCALAR_7), &usdc, &(100 * SCALAR_7));
+    assert_eq!(result.err(), Some(Ok(Error::from_contract_error(515))));
+
+    let withdrawn = fixture.pool.withdraw(&frodo, &(100 * SCALAR_7), &vec![&fixture.env, 49 * SCALAR_7, 490 * SCALAR_7]);
+    assert_approx_eq_abs(withdrawn.get_unchecked(0), 49_8500000, 10);
+    assert_approx_eq_abs(withdrawn.get_unchecked(1), 498_5000000, 100);
+}
+
+#[test]
+fn test_quotes_match_execution() {
+    let fixture = create_fixture_with_data();
+    let henk = Address::generate(&fixture.env);
+
+    fixture.tokens[TokenIndex::XLM].mint(&henk, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(1_000 * SCALAR_7));
+
+    let amounts = vec![&fixture.env, 1_000 * SCALAR_7, 1_000 * SCALAR_7];
+    let deposit_quote = fixture.pool.quote_deposit(&amounts);
+    let minted = fixture.pool.deposit(&henk, &amounts, &0);
+    assert_eq!(deposit_quote.slp_out, minted);
+    assert!(deposit_quote.fee_value > 0);
+
+    // The pool now holds $2,000 of USDC and $1,100 of XLM
+    assert_eq!(deposit_quote.ratios, vec![&fixture.env, 0_6451612, 0_3548388]);
+
+    let withdraw_quote = fixture.pool.quote_withdraw(&minted);
+    let withdrawn = fixture.pool.withdraw(&henk, &minted, &vec![&fixture.env, 0, 0]);
+    assert_eq!(withdraw_quote.amounts, withdrawn);
+    assert!(withdraw_quote.fee_value > 0);
+}
+
+#[test]
+fn test_reserved_liquidity() {
+    let fixture = create_fixture_with_data();
+    let frodo = fixture.users[0].clone();
+    let ben = Address::generate(&fixture.env);
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+
+    assert_eq!(fixture.pool.get_utilization(&usdc), 0);
+    assert_eq!(fixture.pool.get_max_utilization(), 0_9000000);
+
+    // A 5x short reserves 500 of the 1,000 USDC in the pool
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &5_0000000, &xlm, &Side::Short, &0);
+    assert_eq!(fixture.pool.get_token_info(&usdc).reserved, 500 * SCALAR_7);
+    // The 0,3 USDC fee is credited to the supply, so the utilization is just under 50%, 500 / 1,000,3
+    assert_eq!(fixture.pool.get_utilization(&usdc), 0_4998499);
+
+    // Lowering the maximum utilization blocks further borrowing
+    fixture.pool.set_max_utilization(&0_5500000);
+    let result = fixture.position_manager.try_open_position(&ben, &(20 * SCALAR_7), &5_0000000, &xlm, &Side::Short, &0);
+    assert!(result.is_err());
+
+    // Reserved USDC can not be withdrawn
+    let result = fixture.pool.try_withdraw_single(&frodo, &(600 * SCALAR_7), &usdc, &0);
+    assert_eq!(result.err(), Some(Ok(Error::from_contract_error(505))));
+
+    // Liquidating the position releases the reservation
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1200000]);
+    fixture.position_manager.liquidate(&ben, &id, &frodo);
+    assert_eq!(fixture.pool.get_token_info(&usdc).reserved, 0);
+    assert_eq!(fixture.pool.get_utilization(&usdc), 0);
+}
 
-    println!("Balance of ben {:?}", fixture.tokens[TokenIndex::XLM].balance(&ben));
+#[test]
+#[should_panic(expected = "Error(Contract, #516)")]
+fn test_set_max_utilization_invalid() {
+    let fixture = create_fixture_with_data();
+    // A fully borrowable pool is rejected, the supply must keep some free liquidity
+    fixture.pool.set_max_utilization(&SCALAR_7);
+}
 
+#[test]
+fn test_deposit_includes_global_pnl() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+
+    // A 2x short of 100 USDC is 2,000 XLM at 0.10, it loses $20 when XLM rises to 0.11
+    fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0);
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1100000]);
+    assert_eq!(fixture.position_manager.get_global_pnl(), -20 * SCALAR_7);
+
+    // The trader's loss is owed to the pool, so it is added to the value backing the SLP supply
+    let usdc_value = fixture.pool.get_token_info(&usdc).total_supply;
+    let xlm_value = fixture.pool.get_token_info(&xlm).total_supply * 0_1100000 / SCALAR_7;
+    let aum = usdc_value + xlm_value + 20 * SCALAR_7;
+
+    let quote = fixture.pool.quote_deposit(&vec![&fixture.env, 0, 1_000 * SCALAR_7]);
+    let mint_value = 110 * SCALAR_7 - quote.fee_value;
+    let expected = (mint_value * (2_000 * SCALAR_7) / SCALAR_7) * SCALAR_7 / aum;
+    assert_eq!(quote.slp_out, expected);
+}
 
+#[test]
+fn test_withdraw_includes_global_pnl() {
+    let fixture = create_fixture_with_data();
+    let frodo = fixture.users[0].clone();
+    let ben = Address::generate(&fixture.env);
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+
+    // A 2x long of 100 XLM is 200 XLM at 0.10, it gains $4 when XLM rises to 0.12
+    fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1200000]);
+    assert_eq!(fixture.position_manager.get_global_pnl(), 4 * SCALAR_7);
+
+    // The trader's profit is owed by the pool, so it is taken off the value backing the SLP supply
+    let usdc_value = fixture.pool.get_token_info(&usdc).total_supply;
+    let xlm_value = fixture.pool.get_token_info(&xlm).total_supply * 0_1200000 / SCALAR_7;
+    let aum = usdc_value + xlm_value - 4 * SCALAR_7;
+
+    // 100 of the 2,000 SLP tokens are worth 5% of it, paid out in tokens and the fee left in the pool
+    let quote = fixture.pool.quote_withdraw(&(100 * SCALAR_7));
+    let paid_value = quote.amounts.get_unchecked(0) + quote.amounts.get_unchecked(1) * 0_1200000 / SCALAR_7;
+    assert_approx_eq_abs(paid_value + quote.fee_value, aum / 20, 10);
+
+    let withdrawn = fixture.pool.withdraw(&frodo, &(100 * SCALAR_7), &vec![&fixture.env, 0, 0]);
+    assert_eq!(withdrawn, quote.amounts);
 }
 
+#[test]
+fn test_fee_settlement() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    let no_gaps = vec![&fixture.env, 0, 0];
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    assert_eq!(fixture.pool.sync(), no_gaps);
+
+    // The opening fee is paid to the pool and credited to its XLM supply
+    let (id, fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let token_info = fixture.pool.get_token_info(&xlm);
+    assert_eq!(token_info.total_supply, 10_000 * SCALAR_7 + fee);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&fixture.pool.address), 10_000 * SCALAR_7 + fee - 200 * SCALAR_7);
+    assert_eq!(fixture.pool.sync(), no_gaps);
+
+    // The closing fee is paid to the pool as well and the position manager keeps nothing
+    let (payout, close_fee) = fixture.position_manager.close_position(&ben, &id, &0);
+    assert_eq!(payout, 100 * SCALAR_7 - close_fee);
+    assert_eq!(fixture.pool.get_token_info(&xlm).total_supply, 10_000 * SCALAR_7 + fee + close_fee);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&fixture.position_manager.address), 0);
+    assert_eq!(fixture.pool.sync(), no_gaps);
+}
 
+#[test]
+fn test_sync_reports_unaccounted_balance() {
+    let fixture = create_fixture_with_data();
+
+    // Tokens sent straight to the pool are held but not part of its supply
+    fixture.tokens[TokenIndex::USDC].mint(&fixture.pool.address, &(5 * SCALAR_7));
+    assert_eq!(fixture.pool.sync(), vec![&fixture.env, 5 * SCALAR_7, 0]);
+}
+
+#[test]
+fn test_deposit_event() {
+    let fixture = create_fixture_with_data();
+    let henk = Address::generate(&fixture.env);
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(100 * SCALAR_7));
+
+    let amounts = vec![&fixture.env, 100 * SCALAR_7, 0];
+    let quote = fixture.pool.quote_deposit(&amounts);
+    let minted = fixture.pool.deposit(&henk, &amounts, &0);
+
+    let event = fixture.env.events().all().last_unchecked();
+    assert_eq!(event.0, fixture.pool.address);
+    let topics: Vec<Val> = (Symbol::new(&fixture.env, "pool_v1"), Symbol::new(&fixture.env, "deposit"), henk.clone()).into_val(&fixture.env);
+    assert_eq!(event.1, topics);
+    let data: (Vec<i128>, i128, i128, i128) = event.2.into_val(&fixture.env);
+    assert_eq!(data, (amounts, minted, quote.fee_value, 2_000 * SCALAR_7 + minted));
+}
+
+#[test]
+fn test_price_deviation() {
+    let fixture = create_fixture_with_data();
+    let henk = Address::generate(&fixture.env);
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(200 * SCALAR_7));
+    let minted = fixture.pool.deposit(&henk, &vec![&fixture.env, 100 * SCALAR_7, 0], &0);
+
+    // XLM falls from 0.10 to 0.04, a 60% move from the previous oracle record
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_0400000]);
+    let result = fixture.pool.try_swap(&henk, &usdc, &xlm, &(100 * SCALAR_7), &0);
+    assert_eq!(result.err(), Some(Ok(Error::from_contract_error(518))));
+
+    // Liquidity providers can still exit
+    fixture.pool.withdraw(&henk, &(minted / 2), &vec![&fixture.env, 0, 0]);
+
+    fixture.pool.set_price_config(&xlm, &PriceConfig { max_age: 24 * 60 * 60, max_deviation: 0_7500000, twap_records: 1 });
+    assert_eq!(fixture.pool.get_price_config(&xlm).max_deviation, 0_7500000);
+    fixture.pool.swap(&henk, &usdc, &xlm, &(100 * SCALAR_7), &0);
+}
+
+#[test]
+fn test_twap_pricing() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let frodo = fixture.users[0].clone();
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    // At a spot price of $0.11 a 100 USDC deposit mints this much SLP
+    fixture.oracle.set_price_stable(&vec![env, 1_0000000, 0_1100000]);
+    let spot_quote = fixture.pool.quote_deposit(&vec![env, 100 * SCALAR_7, 0]);
+
+    // XLM spikes to $0.13 after $0.09, averaging $0.11 over the last three records
+    fixture.oracle.set_price_stable(&vec![env, 1_0000000, 0_0900000]);
+    fixture.oracle.set_price_stable(&vec![env, 1_0000000, 0_1300000]);
+    fixture.pool.set_price_config(&xlm, &PriceConfig { max_age: 24 * 60 * 60, max_deviation: 0_5000000, twap_records: 3 });
+
+    // SLP is minted at the TWAP, so the spike does not change the deposit
+    let twap_quote = fixture.pool.quote_deposit(&vec![env, 100 * SCALAR_7, 0]);
+    assert_eq!(twap_quote.slp_out, spot_quote.slp_out);
+
+    // A swap pays for XLM at the lower of the $0.13 spot price and the $0.11 TWAP
+    let amount_out = fixture.pool.swap(&frodo, &xlm, &usdc, &(100 * SCALAR_7), &0);
+    assert_approx_eq_abs(amount_out, 11 * SCALAR_7, 0_1000000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #519)")]
+fn test_set_price_config_invalid() {
+    let fixture = create_fixture_with_data();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.pool.set_price_config(&xlm, &PriceConfig { max_age: 60, max_deviation: 0, twap_records: 1 });
+}
+
+#[test]
+fn test_withdraw_with_stale_position_manager_prices() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let henk = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&henk, &(100 * SCALAR_7));
+    let minted = fixture.pool.deposit(&henk, &vec![&fixture.env, 100 * SCALAR_7, 0], &0);
+    fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // A price published 15 minutes ago is too old for the position manager but not for the pool,
+    // which values the open interest at its own prices
+    let published = fixture.env.ledger().timestamp() - 15 * 60;
+    fixture.oracle.set_price(&vec![&fixture.env, 1_0000000, 0_1000000], &published);
+    let result = fixture.position_manager.try_open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    assert_eq!(result.err(), Some(Ok(Error::from_contract_error(604))));
+    fixture.pool.withdraw(&henk, &minted, &vec![&fixture.env, 0, 0]);
+    assert_eq!(fixture.tokens[TokenIndex::SLP].balance(&henk), 0);
+}
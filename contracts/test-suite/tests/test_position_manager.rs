@@ -0,0 +1,881 @@
+#![cfg(test)]
+
+use soroban_sdk::{testutils::{Address as AddressTestTrait, Events}, vec, Address, Error, IntoVal, Symbol, Val, Vec};
+use test_suite::create_fixture_with_data;
+use soroban_sdk::testutils::Ledger;
+use test_suite::dependencies::position_manager::{ClosePositionQuote, DecreaseAmount, FeeConfig, KeeperConfig, Position, PositionLimits, PriceConfig, Side};
+use test_suite::test_fixture::{SCALAR_7, TestFixture, TokenIndex};
+
+#[test]
+fn test_multiple_positions() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(10_000 * SCALAR_7));
+
+    // Ben opens a market position and a resting limit order at the same time
+    let (market_id, _) = fixture.position_manager.open_position(&ben, &(1_000 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let (limit_id, _) = fixture.position_manager.open_limit_position(&ben, &(500 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &0_0500000);
+    assert_ne!(market_id, limit_id);
+
+    let positions = fixture.position_manager.get_positions(&ben);
+    assert_eq!(positions.len(), 2);
+    assert_eq!(positions.get_unchecked(0).id, market_id);
+    assert!(positions.get_unchecked(0).filled);
+    assert_eq!(positions.get_unchecked(1).id, limit_id);
+    assert!(!positions.get_unchecked(1).filled);
+
+    let limit_position = fixture.position_manager.get_position(&ben, &limit_id);
+    assert_eq!(limit_position.collateral, 500 * SCALAR_7);
+    assert_eq!(limit_position.entry_price, 0_0500000);
+
+    // Ids are never reused
+    let (new_id, _) = fixture.position_manager.open_position(&ben, &(1_000 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    assert!(new_id > limit_id);
+    assert_eq!(fixture.position_manager.get_positions(&ben).len(), 3);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #603)")]
+fn test_get_unknown_position() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(10_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(1_000 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    fixture.position_manager.get_position(&ben, &(id + 1));
+}
+
+#[test]
+fn test_short_position_borrows_other_token() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+
+    // Shorting XLM is collateralized in and borrows USDC
+    let pool_usdc = fixture.tokens[TokenIndex::USDC].balance(&fixture.pool.address);
+    let (id, fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0);
+
+    let position = fixture.position_manager.get_position(&ben, &id);
+    assert_eq!(position.side, Side::Short);
+    assert_eq!(position.token, xlm);
+    assert_eq!(position.entry_price, 0_1000000);
+    assert_eq!(position.borrowed, 200 * SCALAR_7);
+    assert_eq!(fixture.tokens[TokenIndex::USDC].balance(&ben), 900 * SCALAR_7 - fee);
+    assert_eq!(fixture.tokens[TokenIndex::USDC].balance(&fixture.pool.address), pool_usdc - 200 * SCALAR_7 + fee);
+}
+
+#[test]
+fn test_liquidate_short() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let liquidator = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(50 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Short, &0);
+
+    // A 10x short loses its collateral when XLM rises ~10%
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1100000]);
+    fixture.position_manager.liquidate(&ben, &id, &liquidator);
+
+    assert_eq!(fixture.position_manager.get_positions(&ben).len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #605)")]
+fn test_short_not_liquidatable_when_price_falls() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let liquidator = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(50 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Short, &0);
+
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_0500000]);
+    fixture.position_manager.liquidate(&ben, &id, &liquidator);
+}
+
+#[test]
+fn test_liquidate_long() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let liquidator = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // A 10x long loses its collateral when XLM falls ~10%
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_0900000]);
+    fixture.position_manager.liquidate(&ben, &id, &liquidator);
+
+    assert_eq!(fixture.position_manager.get_positions(&ben).len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #605)")]
+fn test_long_not_liquidatable_at_entry() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let liquidator = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    fixture.position_manager.liquidate(&ben, &id, &liquidator);
+}
+
+#[test]
+fn test_fill_short_limit_position() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let keeper = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_limit_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0_1200000);
+
+    // A short limit order fills once the price rises to the limit
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1250000]);
+    fixture.position_manager.fill_position(&ben, &id, &keeper);
+
+    let position = fixture.position_manager.get_position(&ben, &id);
+    assert!(position.filled);
+    assert_eq!(position.entry_price, 0_1250000);
+    assert_eq!(position.borrowed, 200 * SCALAR_7);
+}
+
+#[test]
+fn test_cancel_limit_position() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, fee) = fixture.position_manager.open_limit_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &0_0500000);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&ben), 900 * SCALAR_7 - fee);
+
+    // Cancelling refunds the collateral and the prepaid fee
+    let refund = fixture.position_manager.cancel_limit_position(&ben, &id);
+    assert_eq!(refund, 100 * SCALAR_7 + fee);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&ben), 1_000 * SCALAR_7);
+    assert_eq!(fixture.position_manager.get_positions(&ben).len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #610)")]
+fn test_cancel_filled_position() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    fixture.position_manager.cancel_limit_position(&ben, &id);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #606)")]
+fn test_open_limit_position_invalid_price() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.position_manager.open_limit_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #611)")]
+fn test_close_unfilled_position() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    // An open limit order has borrowed nothing, it must be cancelled rather than closed
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_limit_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &0_0500000);
+
+    fixture.position_manager.close_position(&ben, &id, &0);
+}
+
+#[test]
+fn test_fill_position_rewards_keeper() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let keeper = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.position_manager.set_keeper_config(&xlm, &KeeperConfig {
+        fill_share: 0_5000000,
+        liquidation_share: 0_5000000,
+        min_keeper_fee: SCALAR_7,
+    });
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, fee) = fixture.position_manager.open_limit_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &0_1000000);
+
+    // The minimum keeper fee is reserved on top of the collateral and the impact fee
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&ben), 899 * SCALAR_7 - fee);
+    assert_eq!(fixture.position_manager.get_position(&ben, &id).keeper_fee, SCALAR_7);
+
+    // A take profit set while the order is pending is covered by the same reserve
+    fixture.position_manager.add_take_profit(&ben, &id, &0_1200000);
+    fixture.position_manager.fill_position(&ben, &id, &keeper);
+
+    // The keeper filling the order receives half of the fee, the reserve stays with the position
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&keeper), fee / 2);
+    assert_eq!(fixture.position_manager.get_position(&ben, &id).keeper_fee, SCALAR_7);
+
+    // The keeper executing the take profit receives the reserve and its share of the closing fee
+    let tp_keeper = Address::generate(&fixture.env);
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1200000]);
+    fixture.position_manager.fill_position(&ben, &id, &tp_keeper);
+    assert!(fixture.tokens[TokenIndex::XLM].balance(&tp_keeper) > SCALAR_7);
+    assert!(fixture.position_manager.get_positions(&ben).is_empty());
+}
+
+#[test]
+fn test_liquidate_rewards_liquidator() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let liquidator = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.position_manager.set_keeper_config(&xlm, &KeeperConfig {
+        fill_share: 0,
+        liquidation_share: 0_5000000,
+        min_keeper_fee: SCALAR_7,
+    });
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    fixture.position_manager.add_stop_loss(&ben, &id, &0_0800000);
+
+    // Just past the liquidation price, 71.0382520 XLM is left over as the liquidation fee
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_0915000]);
+    fixture.position_manager.liquidate(&ben, &id, &liquidator);
+
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&liquidator), SCALAR_7 + 35519126);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #10)")]
+fn test_set_keeper_config_invalid() {
+    let fixture = create_fixture_with_data();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.position_manager.set_keeper_config(&xlm, &KeeperConfig {
+        fill_share: 1_5000000,
+        liquidation_share: 0,
+        min_keeper_fee: 0,
+    });
+}
+
+#[test]
+fn test_decrease_position() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, open_fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // XLM goes up 25%, Ben takes half of his position off the table
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1250000]);
+    let (payout, fee) = fixture.position_manager.decrease_position(&ben, &id, &DecreaseAmount::Fraction(SCALAR_7 / 2));
+
+    // Half of the 200 XLM borrowed at 0.10 is worth 80 XLM at 0.125
+    // Ben gets 100 borrowed + 50 collateral - 80 repaid - fee
+    assert_eq!(payout, 70 * SCALAR_7 - fee);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&ben), 900 * SCALAR_7 - open_fee + payout);
+
+    let position = fixture.position_manager.get_position(&ben, &id);
+    assert_eq!(position.borrowed, 100 * SCALAR_7);
+    assert_eq!(position.collateral, 50 * SCALAR_7);
+    assert_eq!(position.entry_price, 0_1000000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #10)")]
+fn test_decrease_position_invalid_fraction() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    fixture.position_manager.decrease_position(&ben, &id, &DecreaseAmount::Fraction(SCALAR_7));
+}
+
+#[test]
+fn test_decrease_position_by_collateral() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // Taking 40 XLM of collateral out closes the same share of the borrow at an unchanged price
+    let (payout, fee) = fixture.position_manager.decrease_position(&ben, &id, &DecreaseAmount::Collateral(40 * SCALAR_7));
+    assert_eq!(payout, 40 * SCALAR_7 - fee);
+
+    let position = fixture.position_manager.get_position(&ben, &id);
+    assert_eq!(position.borrowed, 120 * SCALAR_7);
+    assert_eq!(position.collateral, 60 * SCALAR_7);
+    assert_eq!(position.leverage, 2 * SCALAR_7 as u32);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #615)")]
+fn test_decrease_position_below_min_collateral() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // The rest of the position may not be left as dust below the minimum collateral
+    fixture.position_manager.set_position_limits(&xlm, &PositionLimits {
+        min_leverage: SCALAR_7 as u32,
+        max_leverage: (10 * SCALAR_7) as u32,
+        min_collateral: 10 * SCALAR_7,
+        min_notional: 0,
+    });
+    fixture.position_manager.decrease_position(&ben, &id, &DecreaseAmount::Collateral(95 * SCALAR_7));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #612)")]
+fn test_decrease_position_underwater() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // A position worth less than its debt and fees can not be partially closed,
+    // at $0.06 the 200 XLM borrowed at $0.10 cost 333 XLM to repay out of 300
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_0600000]);
+    fixture.position_manager.decrease_position(&ben, &id, &DecreaseAmount::Fraction(0_5000000));
+}
+
+#[test]
+fn test_increase_position() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+    let (long_id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let (short_id, _) = fixture.position_manager.open_position(&ben, &(50 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0);
+
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1250000]);
+    fixture.position_manager.increase_position(&ben, &long_id, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &i128::MAX);
+    fixture.position_manager.increase_position(&ben, &short_id, &(50 * SCALAR_7), &((2 * SCALAR_7) as u32), &0);
+
+    // 200 XLM at 0.10 and 200 XLM at 0.125 average to 0.1125
+    let long = fixture.position_manager.get_position(&ben, &long_id);
+    assert_eq!(long.borrowed, 400 * SCALAR_7);
+    assert_eq!(long.collateral, 200 * SCALAR_7);
+    assert_eq!(long.leverage, (2 * SCALAR_7) as u32);
+    assert_eq!(long.entry_price, 0_1125000);
+
+    // 100 USDC short at 0.10 (1,000 XLM) and 100 USDC at 0.125 (800 XLM) average to 200 / 1,800
+    let short = fixture.position_manager.get_position(&ben, &short_id);
+    assert_eq!(short.borrowed, 200 * SCALAR_7);
+    assert_eq!(short.collateral, 100 * SCALAR_7);
+    assert_eq!(short.entry_price, 0_1111111);
+}
+
+/// Opens a position, waits ten hours and decreases it by half, returning the fee charged.
+/// `before_decrease` can change the pool's utilization right before the decrease.
+fn borrow_fee_after_ten_hours(before_decrease: fn(&TestFixture)) -> i128 {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    fixture.jump(10 * 3600);
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1000000]);
+    before_decrease(&fixture);
+
+    let (_, fee) = fixture.position_manager.decrease_position(&ben, &id, &DecreaseAmount::Fraction(0_5000000));
+    fee
+}
+
+#[test]
+fn test_borrow_fee_accrues_at_historical_rate() {
+    // The fee for the ten hours is charged at the utilization of those ten hours,
+    // not at the utilization when the position is settled
+    let fee = borrow_fee_after_ten_hours(|_| {});
+    assert!(fee > 0);
+
+    // A large borrow right before the decrease
+    assert_eq!(borrow_fee_after_ten_hours(|fixture| {
+        let sam = Address::generate(&fixture.env);
+        let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+        fixture.tokens[TokenIndex::XLM].mint(&sam, &(10_000 * SCALAR_7));
+        fixture.position_manager.open_position(&sam, &(1_000 * SCALAR_7), &((5 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    }), fee);
+
+    // A pool deposit right before the decrease does not reprice the ten hours either
+    assert_eq!(borrow_fee_after_ten_hours(|fixture| {
+        fixture.pool.deposit(&fixture.users[0], &vec![&fixture.env, 1_000 * SCALAR_7, 10_000 * SCALAR_7], &0);
+    }), fee);
+}
+
+#[test]
+fn test_increase_position_fee() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let balance = fixture.tokens[TokenIndex::XLM].balance(&ben);
+
+    // No borrow fee has accrued yet, the 200 XLM borrowed ($25 at 0.125) pay an impact fee of
+    // 0.06% + 0.0000001 = 0.06001% of the amount borrowed
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1250000]);
+    let fee = fixture.position_manager.increase_position(&ben, &id, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &i128::MAX);
+    assert_eq!(fee, 0_1200200);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&ben), balance - 100 * SCALAR_7 - fee);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #613)")]
+fn test_increase_position_invalid_leverage() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    fixture.position_manager.increase_position(&ben, &id, &(100 * SCALAR_7), &((SCALAR_7 / 2) as u32), &i128::MAX);
+}
+
+#[test]
+fn test_add_and_remove_collateral() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, open_fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    fixture.position_manager.add_collateral(&ben, &id, &(100 * SCALAR_7));
+    let position = fixture.position_manager.get_position(&ben, &id);
+    assert_eq!(position.collateral, 200 * SCALAR_7);
+    assert_eq!(position.leverage, (5 * SCALAR_7) as u32);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&ben), 800 * SCALAR_7 - open_fee);
+
+    fixture.position_manager.remove_collateral(&ben, &id, &(150 * SCALAR_7));
+    let position = fixture.position_manager.get_position(&ben, &id);
+    assert_eq!(position.collateral, 50 * SCALAR_7);
+    assert_eq!(position.leverage, (20 * SCALAR_7) as u32);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&ben), 950 * SCALAR_7 - open_fee);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #612)")]
+fn test_remove_collateral_past_maintenance() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // 1,000 XLM borrowed needs 10 XLM of maintenance margin on top of the closing fee,
+    // so 100x leverage is allowed by the limits but leaves the position liquidatable
+    fixture.position_manager.remove_collateral(&ben, &id, &(90 * SCALAR_7));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #613)")]
+fn test_open_position_zero_leverage() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &0, &xlm, &Side::Long, &i128::MAX);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #614)")]
+fn test_open_position_above_max_leverage() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.position_manager.open_position(&ben, &(10 * SCALAR_7), &((101 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+}
+
+#[test]
+fn test_position_limits_per_token() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    let limits = PositionLimits {
+        min_leverage: (2 * SCALAR_7) as u32,
+        max_leverage: (10 * SCALAR_7) as u32,
+        min_collateral: 10 * SCALAR_7,
+        min_notional: 50 * SCALAR_7,
+    };
+    fixture.position_manager.set_position_limits(&xlm, &limits);
+    assert_eq!(fixture.position_manager.get_position_limits(&xlm), limits);
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let open = |collateral: i128, leverage: i128| {
+        fixture.position_manager.try_open_position(&ben, &collateral, &(leverage as u32), &xlm, &Side::Long, &i128::MAX)
+    };
+    assert_eq!(open(100 * SCALAR_7, SCALAR_7).err().unwrap().unwrap(), soroban_sdk::Error::from_contract_error(613));
+    assert_eq!(open(10 * SCALAR_7, 11 * SCALAR_7).err().unwrap().unwrap(), soroban_sdk::Error::from_contract_error(614));
+    assert_eq!(open(5 * SCALAR_7, 10 * SCALAR_7).err().unwrap().unwrap(), soroban_sdk::Error::from_contract_error(615));
+    assert_eq!(open(20 * SCALAR_7, 2 * SCALAR_7).err().unwrap().unwrap(), soroban_sdk::Error::from_contract_error(616));
+    assert!(open(10 * SCALAR_7, 5 * SCALAR_7).is_ok());
+
+    // Other tokens keep the defaults
+    let usdc = fixture.tokens[TokenIndex::USDC].address.clone();
+    assert_eq!(fixture.position_manager.get_position_limits(&usdc).max_leverage, (100 * SCALAR_7) as u32);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #614)")]
+fn test_fill_position_checks_limits() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let keeper = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_limit_position(&ben, &(100 * SCALAR_7), &((20 * SCALAR_7) as u32), &xlm, &Side::Long, &0_1000000);
+
+    fixture.position_manager.set_position_limits(&xlm, &PositionLimits {
+        min_leverage: SCALAR_7 as u32,
+        max_leverage: (10 * SCALAR_7) as u32,
+        min_collateral: 1,
+        min_notional: 0,
+    });
+    fixture.position_manager.fill_position(&ben, &id, &keeper);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #10)")]
+fn test_set_position_limits_invalid() {
+    let fixture = create_fixture_with_data();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.position_manager.set_position_limits(&xlm, &PositionLimits {
+        min_leverage: (10 * SCALAR_7) as u32,
+        max_leverage: (2 * SCALAR_7) as u32,
+        min_collateral: 1,
+        min_notional: 0,
+    });
+}
+
+#[test]
+fn test_set_fee_config() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    let config = fixture.position_manager.get_fee_config();
+    assert_eq!(config.base_fee, 6000);
+    assert_eq!(config.hourly_base_fee, 800);
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (_, fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // Raising the base fee by 0.1% applies to new positions without a redeploy,
+    // the impact fee rate is charged on the 200 XLM borrowed
+    fixture.position_manager.set_fee_config(&FeeConfig {
+        base_fee: 16000,
+        ..config
+    });
+    assert_eq!(fixture.position_manager.get_fee_config().base_fee, 16000);
+
+    let (_, new_fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    assert_eq!(fee, 0_1200200);
+    assert_eq!(new_fee, fee + 0_2000000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #10)")]
+fn test_set_fee_config_invalid() {
+    let fixture = create_fixture_with_data();
+
+    fixture.position_manager.set_fee_config(&FeeConfig {
+        base_fee: 6000,
+        hourly_base_fee: 800,
+        impact_fee_scalar: 0,
+    });
+}
+
+#[test]
+fn test_open_position_acceptable_price() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+
+    // XLM trades at 0.10, a long may not enter above and a short may not enter below the acceptable price
+    let long = fixture.position_manager.try_open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &0_0990000);
+    assert_eq!(long.err(), Some(Ok(Error::from_contract_error(617))));
+    let short = fixture.position_manager.try_open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0_1010000);
+    assert_eq!(short.err(), Some(Ok(Error::from_contract_error(617))));
+
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &0_1000000);
+    assert_eq!(fixture.position_manager.get_position(&ben, &id).entry_price, 0_1000000);
+}
+
+#[test]
+fn test_increase_position_acceptable_price() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+    let (long_id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let (short_id, _) = fixture.position_manager.open_position(&ben, &(50 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0);
+
+    // XLM moves to 0.125, past the price the user accepts to add to either position at
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1250000]);
+    let long = fixture.position_manager.try_increase_position(&ben, &long_id, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &0_1200000);
+    assert_eq!(long.err(), Some(Ok(Error::from_contract_error(617))));
+    let short = fixture.position_manager.try_increase_position(&ben, &short_id, &(50 * SCALAR_7), &((2 * SCALAR_7) as u32), &0_1300000);
+    assert_eq!(short.err(), Some(Ok(Error::from_contract_error(617))));
+
+    fixture.position_manager.increase_position(&ben, &long_id, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &0_1250000);
+    assert_eq!(fixture.position_manager.get_position(&ben, &long_id).borrowed, 400 * SCALAR_7);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #618)")]
+fn test_close_position_payout_too_low() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // The price of XLM drops so the payout is less than the collateral
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_000_0000, 0_0900000]);
+    fixture.position_manager.close_position(&ben, &id, &(100 * SCALAR_7));
+}
+
+#[test]
+fn test_quotes_match_execution() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let liquidator = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+
+    let open_quote = fixture.position_manager.quote_open_position(&(100 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Long);
+    let (id, fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((10 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let position = fixture.position_manager.get_position(&ben, &id);
+    assert_eq!(open_quote.fee, fee);
+    assert_eq!(open_quote.entry_price, position.entry_price);
+    assert_eq!(open_quote.borrowed, position.borrowed);
+    assert!(open_quote.liquidation_price < position.entry_price && open_quote.liquidation_price > 0_0900000);
+
+    // At the entry price the position repays what it borrowed and pays the closing fee out of its collateral
+    let close_quote = fixture.position_manager.quote_close_position(&ben, &id);
+    assert_eq!(close_quote.price, position.entry_price);
+    assert_eq!(close_quote.to_repay, position.borrowed);
+    assert_eq!(close_quote.payout, position.collateral - close_quote.fee);
+
+    let liquidation_quote = fixture.position_manager.quote_liquidation(&ben, &id);
+    assert!(!liquidation_quote.liquidatable);
+    assert_eq!(liquidation_quote.liquidation_price, open_quote.liquidation_price);
+
+    // The liquidator is paid exactly the quoted reward
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_0900000]);
+    let liquidation_quote = fixture.position_manager.quote_liquidation(&ben, &id);
+    assert!(liquidation_quote.liquidatable);
+    fixture.position_manager.liquidate(&ben, &id, &liquidator);
+    assert_eq!(fixture.tokens[TokenIndex::XLM].balance(&liquidator), liquidation_quote.keeper_reward);
+}
+
+#[test]
+fn test_global_pnl() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(1_000 * SCALAR_7));
+
+    // A 2x long of 100 XLM and a 2x short of 50 USDC, both at 0.10
+    fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let (short_id, _) = fixture.position_manager.open_position(&ben, &(50 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0);
+
+    let open_interest = fixture.position_manager.get_open_interest(&xlm);
+    assert_eq!(open_interest.long_size, 200 * SCALAR_7);
+    assert_eq!(open_interest.long_notional, 20 * SCALAR_7);
+    assert_eq!(open_interest.short_size, 1_000 * SCALAR_7);
+    assert_eq!(open_interest.short_notional, 100 * SCALAR_7);
+    assert_eq!(fixture.position_manager.get_global_pnl(), 0);
+
+    // XLM rises to 0.12, the long gains 200 * 0.02 = $4 and the short loses 1,000 * 0.02 = $20
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_1200000]);
+    assert_eq!(fixture.position_manager.get_global_pnl(), -16 * SCALAR_7);
+
+    // Closing half of the short halves its open interest and the loss it contributes
+    fixture.position_manager.decrease_position(&ben, &short_id, &DecreaseAmount::Fraction(0_5000000));
+    let open_interest = fixture.position_manager.get_open_interest(&xlm);
+    assert_eq!(open_interest.short_size, 500 * SCALAR_7);
+    assert_eq!(open_interest.short_notional, 50 * SCALAR_7);
+    assert_eq!(fixture.position_manager.get_global_pnl(), -6 * SCALAR_7);
+}
+
+
+#[test]
+fn test_position_events() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+
+    // Opening emits the new position and the fee paid
+    let (id, fee) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let event = fixture.env.events().all().last_unchecked();
+    assert_eq!(event.0, fixture.position_manager.address);
+    let topics: Vec<Val> = (Symbol::new(&fixture.env, "pm_v1"), Symbol::new(&fixture.env, "open_position"), ben.clone()).into_val(&fixture.env);
+    assert_eq!(event.1, topics);
+    let (position, event_fee): (Position, i128) = event.2.into_val(&fixture.env);
+    assert_eq!(position, fixture.position_manager.get_position(&ben, &id));
+    assert_eq!(event_fee, fee);
+
+    // Closing emits the price, repayment, fee and payout
+    let quote = fixture.position_manager.quote_close_position(&ben, &id);
+    fixture.position_manager.close_position(&ben, &id, &0);
+    let event = fixture.env.events().all().last_unchecked();
+    let topics: Vec<Val> = (Symbol::new(&fixture.env, "pm_v1"), Symbol::new(&fixture.env, "close_position"), ben.clone()).into_val(&fixture.env);
+    assert_eq!(event.1, topics);
+    let (event_id, close): (u32, ClosePositionQuote) = event.2.into_val(&fixture.env);
+    assert_eq!(event_id, id);
+    assert_eq!(close, quote);
+}
+
+#[test]
+fn test_twap_execution_prices() {
+    let fixture = create_fixture_with_data();
+    let env = &fixture.env;
+    let ben = Address::generate(env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(100 * SCALAR_7));
+
+    // XLM spikes to $0.13, its TWAP over the last three records is $0.11
+    fixture.oracle.set_price_stable(&vec![env, 1_0000000, 0_1000000]);
+    fixture.oracle.set_price_stable(&vec![env, 1_0000000, 0_1300000]);
+    fixture.position_manager.set_price_config(&xlm, &PriceConfig { max_age: 10 * 60, max_deviation: 0_5000000, twap_records: 3 });
+
+    // Traders open at the worse of the spot price and the TWAP for them
+    let (long_id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    let (short_id, _) = fixture.position_manager.open_position(&ben, &(10 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0);
+    assert_eq!(fixture.position_manager.get_position(&ben, &long_id).entry_price, 0_1300000);
+    assert_eq!(fixture.position_manager.get_position(&ben, &short_id).entry_price, 0_1100000);
+
+    // And close at the worse of the two as well
+    assert_eq!(fixture.position_manager.quote_close_position(&ben, &long_id).price, 0_1100000);
+    assert_eq!(fixture.position_manager.quote_close_position(&ben, &short_id).price, 0_1300000);
+
+    // Liquidations are checked at the spot price
+    assert_eq!(fixture.position_manager.quote_liquidation(&ben, &long_id).price, 0_1300000);
+
+    // A take profit triggers at the exit price, so the $0.13 spike does not reach a $0.12 target
+    let keeper = Address::generate(env);
+    fixture.position_manager.add_take_profit(&ben, &long_id, &0_1200000);
+    fixture.position_manager.fill_position(&ben, &long_id, &keeper);
+    assert_eq!(fixture.position_manager.get_positions(&ben).len(), 2);
+
+    // Limit orders fill at the entry price, the spike does not fill a short limit at $0.12
+    let (limit_id, _) = fixture.position_manager.open_limit_position(&ben, &(10 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0_1200000);
+    let result = fixture.position_manager.try_fill_position(&ben, &limit_id, &keeper);
+    assert_eq!(result.err(), Some(Ok(Error::from_contract_error(611))));
+
+    // A short limit at $0.105 fills at the $0.11 TWAP
+    let (limit_id, _) = fixture.position_manager.open_limit_position(&ben, &(10 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Short, &0_1050000);
+    fixture.position_manager.fill_position(&ben, &limit_id, &keeper);
+    assert_eq!(fixture.position_manager.get_position(&ben, &limit_id).entry_price, 0_1100000);
+}
+
+#[test]
+fn test_stale_price() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    fixture.tokens[TokenIndex::USDC].mint(&ben, &(100 * SCALAR_7));
+
+    // A price published 15 minutes ago is too old to open a position but not to deposit into the pool
+    let published = fixture.env.ledger().timestamp() - 15 * 60;
+    fixture.oracle.set_price(&vec![&fixture.env, 1_0000000, 0_1000000], &published);
+    fixture.pool.deposit(&ben, &vec![&fixture.env, 100 * SCALAR_7, 0], &0);
+    let result = fixture.position_manager.try_open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    assert_eq!(result.err(), Some(Ok(Error::from_contract_error(604))));
+
+    // The admin can allow older prices per asset
+    let config = PriceConfig { max_age: 30 * 60, max_deviation: 0_5000000, twap_records: 1 };
+    fixture.position_manager.set_price_config(&xlm, &config);
+    fixture.position_manager.set_price_config(&fixture.tokens[TokenIndex::USDC].address, &config);
+    fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+}
+
+#[test]
+fn test_price_deviation() {
+    let fixture = create_fixture_with_data();
+    let ben = Address::generate(&fixture.env);
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.tokens[TokenIndex::XLM].mint(&ben, &(1_000 * SCALAR_7));
+    let (id, _) = fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+
+    // XLM doubles from one oracle record to the next, more than the default 50% deviation
+    fixture.env.ledger().with_mut(|ledger| ledger.timestamp += 300);
+    fixture.oracle.set_price_stable(&vec![&fixture.env, 1_0000000, 0_2000000]);
+    let result = fixture.position_manager.try_open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+    assert_eq!(result.err(), Some(Ok(Error::from_contract_error(619))));
+
+    // Closing only reduces exposure and is not guarded
+    fixture.position_manager.close_position(&ben, &id, &0);
+
+    fixture.position_manager.set_price_config(&xlm, &PriceConfig { max_age: 10 * 60, max_deviation: SCALAR_7, twap_records: 1 });
+    assert_eq!(fixture.position_manager.get_price_config(&xlm).max_deviation, SCALAR_7);
+    fixture.position_manager.open_position(&ben, &(100 * SCALAR_7), &((2 * SCALAR_7) as u32), &xlm, &Side::Long, &i128::MAX);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #10)")]
+fn test_set_price_config_invalid() {
+    let fixture = create_fixture_with_data();
+    let xlm = fixture.tokens[TokenIndex::XLM].address.clone();
+    fixture.position_manager.set_price_config(&xlm, &PriceConfig { max_age: 0, max_deviation: 0_5000000, twap_records: 1 });
+}